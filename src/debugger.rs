@@ -0,0 +1,100 @@
+use std::{
+    collections::HashSet,
+    io::{stdin, stdout, Write},
+};
+
+use crate::{
+    interpreter::Interpreter,
+    token::{Token, TokenType},
+    value::Value,
+};
+
+const DEBUGGER_HELP: &str = "\
+step, s           Run until the next statement.
+continue, c       Run until the next breakpoint.
+print, p <name>   Show the value of a variable.
+backtrace, bt     Show the calls being evaluated.
+break, b <line>   Pause whenever a statement on this line runs.
+help, h           Show this message.";
+
+/// A line-based step debugger, driven from stdin before each statement runs.
+pub struct Debugger {
+    stepping: bool,
+    // Lines as the user sees them, starting from 1.
+    breakpoints: HashSet<usize>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            stepping: true,
+            breakpoints: HashSet::new(),
+        }
+    }
+
+    pub fn before_statement(&mut self, interpreter: &Interpreter, line: usize) {
+        let line = line + 1;
+        if !self.stepping && !self.breakpoints.contains(&line) {
+            return;
+        }
+
+        println!("[line {line}]");
+        let mut input = String::new();
+        loop {
+            print!("debug> ");
+            stdout().flush().ok();
+            input.clear();
+            // Stdin closing is treated like continuing without breakpoints.
+            if stdin().read_line(&mut input).unwrap_or(0) == 0 {
+                self.stepping = false;
+                self.breakpoints.clear();
+                return;
+            }
+
+            let (command, arg) = match input.trim().split_once(char::is_whitespace) {
+                Some((command, arg)) => (command, arg.trim()),
+                None => (input.trim(), ""),
+            };
+            match command {
+                "" | "step" | "s" => {
+                    self.stepping = true;
+                    return;
+                }
+                "continue" | "c" => {
+                    self.stepping = false;
+                    return;
+                }
+                "print" | "p" => Self::print_variable(interpreter, arg, line),
+                "backtrace" | "bt" => Self::print_backtrace(interpreter, line),
+                "break" | "b" => match arg.parse::<usize>() {
+                    Ok(x) => {
+                        self.breakpoints.insert(x);
+                        println!("Breakpoint set at line {x}.");
+                    }
+                    Err(_) => println!("Usage: break <line>"),
+                },
+                "help" | "h" => println!("{DEBUGGER_HELP}"),
+                _ => println!("Unknown command '{command}'. Type help for a list of commands."),
+            }
+        }
+    }
+
+    fn print_variable(interpreter: &Interpreter, name: &str, line: usize) {
+        if name.is_empty() {
+            println!("Usage: print <name>");
+            return;
+        }
+        let token = Token::new(TokenType::Identifier, name.to_owned(), Value::None, line);
+        match interpreter.get_current_env().borrow().get(&token) {
+            Ok(x) => println!("{name} = {x}"),
+            Err(x) => println!("{x}"),
+        }
+    }
+
+    fn print_backtrace(interpreter: &Interpreter, line: usize) {
+        println!("  at line {line}");
+        for (func_name, call_line) in interpreter.get_call_stack().iter().rev() {
+            println!("  in {func_name}, called at line {}", call_line + 1);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::{
-    error::{Result, RuntimeError},
+    error::{Result, RuntimeError, RuntimeErrorKind},
     token::Token,
     value::Value,
 };
@@ -70,7 +70,23 @@ impl<'a> Environment {
 
     fn undef_var_err<T>(name: &Token) -> Result<T> {
         let msg = format!("Undefined variable '{}'", &name.lexeme);
-        Err(RuntimeError::new(name.clone(), msg))
+        Err(RuntimeError::new(
+            name.clone(),
+            RuntimeErrorKind::UndefinedVariable,
+            msg,
+        ))
+    }
+
+    /// The entries sorted by name, so listings don't change order between runs.
+    pub fn sorted_entries(&self) -> Vec<(&String, &Value)> {
+        let mut entries = self.values.iter().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|(name, _)| *name);
+        entries
+    }
+
+    /// Whether the name is defined in this environment, ignoring enclosing ones.
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
     }
 
     pub fn define(&mut self, name: String, value: Value) {
@@ -1,4 +1,4 @@
-use crate::token::Token;
+use crate::{token::Token, value::Value};
 use once_cell::sync::Lazy;
 use std::{
     error::Error,
@@ -9,19 +9,93 @@ use std::{
 
 pub type Result<T> = std::result::Result<T, RuntimeError>;
 
+/// What went wrong, so errors can be told apart without matching on their message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    Syntax,
+    TypeError,
+    UndefinedVariable,
+    UndefinedProperty,
+    ArityMismatch,
+    NotCallable,
+    StackOverflow,
+    ExecutionLimit,
+    Thrown,
+    Native,
+}
+
+impl Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Self::Syntax => "SyntaxError",
+            Self::TypeError => "TypeError",
+            Self::UndefinedVariable => "UndefinedVariable",
+            Self::UndefinedProperty => "UndefinedProperty",
+            Self::ArityMismatch => "ArityMismatch",
+            Self::NotCallable => "NotCallable",
+            Self::StackOverflow => "StackOverflow",
+            Self::ExecutionLimit => "ExecutionLimit",
+            Self::Thrown => "Thrown",
+            Self::Native => "NativeError",
+        };
+        f.write_str(text)
+    }
+}
+
 #[derive(Debug)]
 pub struct RuntimeError {
-    token: Token,
+    kind: RuntimeErrorKind,
+    line: usize,
     msg: String,
+    thrown: Option<Box<Value>>,
+    // (function name, call line) for each call the error unwound through, innermost first.
+    call_stack: Vec<(String, usize)>,
 }
 
 impl RuntimeError {
-    pub fn new(token: Token, msg: impl ToString) -> Self {
+    pub fn new(token: Token, kind: RuntimeErrorKind, msg: impl ToString) -> Self {
+        Self {
+            kind,
+            line: token.line,
+            msg: msg.to_string(),
+            thrown: None,
+            call_stack: vec![],
+        }
+    }
+
+    pub fn at_line(line: usize, kind: RuntimeErrorKind, msg: impl ToString) -> Self {
         Self {
-            token,
+            kind,
+            line,
             msg: msg.to_string(),
+            thrown: None,
+            call_stack: vec![],
         }
     }
+
+    pub fn thrown(token: Token, value: Value) -> Self {
+        Self {
+            kind: RuntimeErrorKind::Thrown,
+            line: token.line,
+            msg: value.to_string(),
+            thrown: Some(Box::new(value)),
+            call_stack: vec![],
+        }
+    }
+
+    pub fn kind(&self) -> RuntimeErrorKind {
+        self.kind
+    }
+
+    pub fn with_call_frame(mut self, func_name: String, call_line: usize) -> Self {
+        self.call_stack.push((func_name, call_line));
+        self
+    }
+
+    /// The value a catch block receives: the thrown value, or the message for built-in errors.
+    pub fn into_value(self) -> Value {
+        self.thrown.map_or(Value::String(self.msg), |x| *x)
+    }
 }
 
 impl Display for RuntimeError {
@@ -32,15 +106,6 @@ impl Display for RuntimeError {
 
 impl Error for RuntimeError {}
 
-impl<S: ToString> From<(Token, S)> for RuntimeError {
-    fn from(x: (Token, S)) -> Self {
-        Self {
-            token: x.0,
-            msg: x.1.to_string(),
-        }
-    }
-}
-
 static ERR_HANDLER: Lazy<Mutex<Box<(dyn ErrorHandler + Sync + Send)>>> = Lazy::new(|| {
     Mutex::new(Box::new(StdErrorHandler {
         had_error: false,
@@ -54,8 +119,10 @@ pub fn get_err_handler<'a>() -> MutexGuard<'a, Box<(dyn ErrorHandler + Send + Sy
 
 pub trait ErrorHandler {
     fn had_error(&self) -> bool;
-    fn report_native(&self, func_name: &str, err: Box<dyn Error>, call_line: usize);
+    /// Forgets earlier errors, so a REPL line isn't skipped because of a previous one.
+    fn clear_error(&mut self);
     fn report(&self, line: usize, msg: &str);
+    fn warning(&self, token: Token, msg: &str);
     fn error(&mut self, token: Token, msg: &str);
     fn runtime_error(&mut self, err: RuntimeError);
 }
@@ -70,12 +137,8 @@ impl ErrorHandler for StdErrorHandler {
         self.had_error
     }
 
-    fn report_native(&self, func_name: &str, err: Box<dyn Error>, call_line: usize) {
-        stderr()
-            .write_fmt(format_args!(
-                "\nError from <{func_name}>:\n{err}\ncalled at line {call_line}\n\n"
-            ))
-            .ok();
+    fn clear_error(&mut self) {
+        self.had_error = false;
     }
 
     fn report(&self, mut line: usize, msg: &str) {
@@ -85,13 +148,38 @@ impl ErrorHandler for StdErrorHandler {
             .ok();
     }
 
+    fn warning(&self, token: Token, msg: &str) {
+        stderr()
+            .write_fmt(format_args!(
+                "\nWarning: {msg} at line {}\n\n",
+                token.line + 1
+            ))
+            .ok();
+    }
+
     fn error(&mut self, token: Token, msg: &str) {
         self.report(token.line, msg);
         self.had_error = true;
     }
 
     fn runtime_error(&mut self, err: RuntimeError) {
-        self.report(err.token.line, &err.msg);
+        // Runs of the same frame, like deep recursion, are shown once with a count.
+        let mut trace = String::new();
+        for frames in err.call_stack.chunk_by(|a, b| a == b) {
+            let (func_name, call_line) = &frames[0];
+            trace += &format!("  in {func_name}, called at line {}\n", call_line + 1);
+            if frames.len() > 1 {
+                trace += &format!("  ... repeated {} more times\n", frames.len() - 1);
+            }
+        }
+        stderr()
+            .write_fmt(format_args!(
+                "\n{}: {} at line {}\n{trace}\n",
+                err.kind,
+                err.msg,
+                err.line + 1
+            ))
+            .ok();
         self.had_runtime_error = true;
     }
 }
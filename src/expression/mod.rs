@@ -26,6 +26,22 @@ impl Expression {
         }
     }
 
+    /// The line of the first token the expression keeps, literals don't keep any.
+    pub fn get_line(&self) -> Option<usize> {
+        match self {
+            Expression::Binary(x) => x.left.get_line().or(Some(x.operator.line)),
+            Expression::Call(x) => x.callee.get_line().or(Some(x.paren.line)),
+            Expression::Grouping(x) => x.expr.get_line(),
+            Expression::Literal(_) => None,
+            Expression::Unary(x) => Some(x.operator.line),
+            Expression::Variable(x) => Some(x.name.line),
+            Expression::Assign(x) => Some(x.name.line),
+            Expression::Logical(x) => x.left.get_line().or(Some(x.operator.line)),
+            Expression::Get(x) => x.object.get_line().or(Some(x.name.line)),
+            Expression::Set(x) => x.object.get_line().or(Some(x.name.line)),
+        }
+    }
+
     pub fn set_scope_depth(&mut self, value: u32) {
         match self {
             Expression::Call(x) => x.scope_depth = Some(value),
@@ -1,15 +1,21 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use crate::{
+    debugger::Debugger,
     environment::{EnvPtr, Environment, GetDeep},
-    error::{get_err_handler, Result},
+    error::{get_err_handler, Result, RuntimeError, RuntimeErrorKind},
     expression::{
         AssignExpression, BinaryExpression, CallExpression, Expression, GetExpression,
         LogicalExpression, SetExpression, UnaryExpression, VariableExpression,
     },
     statement::{
-        BlockStatement, ClassStatement, ExpressionStatement, FunctionStatement, IfStatement,
-        ReturnStatement, Statement, VarStatement, WhileStatement,
+        BlockStatement, BreakStatement, ClassStatement, ContinueStatement, ExpressionStatement,
+        ForInStatement, ForStatement, FunctionStatement, IfStatement, ReturnStatement, Statement,
+        ThrowStatement, TryStatement, VarStatement, WhileStatement,
     },
     token::{Token, TokenType},
     value::{
@@ -18,9 +24,17 @@ use crate::{
     },
 };
 
+/// Calls nested deeper than this are a runtime error instead of overflowing the native stack.
+pub const MAX_CALL_DEPTH: usize = 256;
+
+/// The longest string, in bytes, that repeating a string with `*` will build.
+pub const MAX_REPEAT_LEN: usize = 100_000_000;
+
 pub enum MaybeReturn {
     Normal(Value),
     Return(Value),
+    Break(Option<String>),
+    Continue(Option<String>),
 }
 
 impl From<()> for MaybeReturn {
@@ -31,7 +45,22 @@ impl From<()> for MaybeReturn {
 
 pub struct Interpreter {
     globals: EnvPtr,
+    // Globals declared with const. Kept here rather than in the resolver, so they stay constant
+    // in later REPL lines and loaded files.
+    global_constants: HashSet<String>,
+    // Globals set by the host with set_global or register_native, defined again after a reset.
+    host_globals: HashMap<String, Value>,
     env: EnvPtr,
+    strict: bool,
+    warn_unused: bool,
+    // (function name, call line) for each call being evaluated, outermost first.
+    call_stack: Vec<(String, usize)>,
+    debugger: Option<Debugger>,
+    trace: bool,
+    // Statements and blocks left to run before stopping, no limit when None.
+    fuel: Option<u64>,
+    // Line of the last statement that kept one, for errors that have no token.
+    line: usize,
 }
 
 impl Interpreter {
@@ -39,18 +68,118 @@ impl Interpreter {
         let globals = Rc::new(RefCell::new(Environment::new(None)));
         Self {
             globals: globals.clone(),
+            global_constants: HashSet::new(),
+            host_globals: HashMap::new(),
             env: globals,
+            strict: false,
+            warn_unused: false,
+            call_stack: vec![],
+            debugger: None,
+            trace: false,
+            fuel: None,
+            line: 0,
         }
     }
 
-    pub fn register_native(&self, func: NativeFunction) {
-        let mut env = self.env.borrow_mut();
-        env.define(
-            func.get_name().to_owned(),
-            Value::NativeFunction(SharedPtr::new(func)),
+    /// In strict mode the resolver turns undefined globals into errors and checks that functions return on every path.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Makes the resolver warn about locals that are declared but never read.
+    pub fn set_warn_unused(&mut self, warn_unused: bool) {
+        self.warn_unused = warn_unused;
+    }
+
+    pub fn warns_unused(&self) -> bool {
+        self.warn_unused
+    }
+
+    /// Pauses before the first statement and lets the user step through the program.
+    pub fn enable_debugger(&mut self) {
+        self.debugger = Some(Debugger::new());
+    }
+
+    /// Prints each statement to stderr before it runs, along with the calls it's nested in.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    fn trace_statement(&self, statement: &Statement) {
+        let calls = self
+            .call_stack
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(" > ");
+        eprintln!(
+            "[line {}] {} [{calls}]",
+            self.line + 1,
+            statement.kind_name()
         );
     }
 
+    /// Limits how many statements and blocks can run, to stop runaway scripts.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    fn consume_fuel(&mut self) -> Result<()> {
+        match &mut self.fuel {
+            Some(0) => Err(RuntimeError::at_line(
+                self.line,
+                RuntimeErrorKind::ExecutionLimit,
+                "Execution limit exceeded.",
+            )),
+            Some(x) => {
+                *x -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn get_call_stack(&self) -> &[(String, usize)] {
+        &self.call_stack
+    }
+
+    pub fn register_native(&mut self, func: NativeFunction) {
+        let name = func.get_name().to_owned();
+        self.set_global(&name, Value::NativeFunction(SharedPtr::new(func)));
+    }
+
+    pub fn is_global_constant(&self, name: &str) -> bool {
+        self.global_constants.contains(name)
+    }
+
+    pub fn global_constants_mut(&mut self) -> &mut HashSet<String> {
+        &mut self.global_constants
+    }
+
+    /// Defines a global, or replaces it, so Rust code can hand values to scripts.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.host_globals.insert(name.to_owned(), value.clone());
+        self.globals.borrow_mut().define(name.to_owned(), value);
+    }
+
+    /// Forgets the globals scripts defined, to run unrelated scripts one after another. Globals
+    /// from register_native and set_global get back the value they were set to.
+    pub fn reset(&mut self) {
+        let mut globals = self.globals.borrow_mut();
+        *globals = Environment::new(None);
+        for (name, value) in &self.host_globals {
+            globals.define(name.clone(), value.clone());
+        }
+        drop(globals);
+        self.global_constants.clear();
+        self.env = self.globals.clone();
+        self.call_stack.clear();
+    }
+
     pub fn get_global_env(&self) -> EnvPtr {
         self.globals.clone()
     }
@@ -72,69 +201,95 @@ impl Interpreter {
         }
     }
 
-    fn is_truthy(val: &Value) -> bool {
+    /// None, false, the empty string and the empty list are falsy, everything else is truthy.
+    /// Numbers are always truthy, including 0.
+    pub fn is_truthy(val: &Value) -> bool {
         match val {
             Value::None => false,
             Value::Boolean(x) => *x,
+            Value::String(x) => !x.is_empty(),
+            Value::List(x) => !x.borrow().is_empty(),
             _ => true,
         }
     }
 
-    fn error<T>(token: Token, msg: impl ToString) -> Result<T> {
-        Err((token, msg).into())
+    fn error<T>(token: Token, kind: RuntimeErrorKind, msg: impl ToString) -> Result<T> {
+        Err(RuntimeError::new(token, kind, msg))
     }
 
     fn eval_unary(&mut self, expr: &UnaryExpression) -> Result<Value> {
         let right = self.evaluate(&expr.right)?;
-        let val = match expr.operator.token_type {
-            TokenType::Minus => {
-                let val = match right {
-                    Value::Number(x) => x,
-                    _ => {
-                        return Self::error(
-                            expr.operator.clone(),
-                            "Minus unary operator can only be used on numbers.",
-                        )
-                    }
-                };
-                Value::Number(-val)
-            }
-            TokenType::Not => Value::Boolean(!Self::is_truthy(&right)),
-            _ => {
+        let val = match (expr.operator.token_type, right) {
+            (TokenType::Minus, Value::Number(x)) => Value::Number(-x),
+            // Unary plus leaves the number as is, it only checks the operand.
+            (TokenType::Plus, Value::Number(x)) => Value::Number(x),
+            (TokenType::Not, x) => Value::Boolean(!Self::is_truthy(&x)),
+            (_, x) => {
                 return Self::error(
                     expr.operator.clone(),
-                    "Minus unary operator can only be used on numbers.",
+                    RuntimeErrorKind::TypeError,
+                    format!(
+                        "Unary '{}' can only be used on numbers, but got '{x}'.",
+                        expr.operator.lexeme
+                    ),
                 )
             }
         };
         Ok(val)
     }
 
-    fn is_equal(a: Value, b: Value) -> bool {
-        match a {
-            Value::None => match b {
-                Value::None => true,
-                _ => false,
-            },
-            Value::Boolean(x) => match b {
-                Value::Boolean(y) => y == x,
-                _ => false,
-            },
-            Value::Number(x) => match b {
-                Value::Number(y) => x == y,
-                _ => false,
-            },
-            Value::String(x) => match b {
-                Value::String(y) => x == y,
-                _ => false,
-            },
-            Value::NativeFunction(_) => false, //TODO
-            Value::Function(_) => false,       //TODO
-            Value::Class(_) => false,          //TODO
-            Value::Instance(_) => false,       //TODO
+    /// The `is` comparison. Strings, numbers, booleans and none compare by value, since they
+    /// aren't shared. Lists, functions, instances and foreign values compare by identity.
+    pub fn is_equal(a: Value, b: Value) -> bool {
+        match (a, b) {
+            (Value::None, Value::None) => true,
+            (Value::Boolean(x), Value::Boolean(y)) => x == y,
+            (Value::Number(x), Value::Number(y)) => x == y,
+            (Value::String(x), Value::String(y)) => x == y,
+            (Value::NativeFunction(x), Value::NativeFunction(y)) => x.ptr_eq(&y),
+            (Value::Function(x), Value::Function(y)) => x.ptr_eq(&y),
+            // Classes are copied around by value, their name is what identifies them.
+            (Value::Class(x), Value::Class(y)) => x.name == y.name,
+            (Value::Instance(x), Value::Instance(y)) => x.ptr_eq(&y),
+            (Value::List(x), Value::List(y)) => x.ptr_eq(&y),
+            (Value::Foreign(x), Value::Foreign(y)) => x.ptr_eq(&y),
+            _ => false,
         }
     }
 
+    /// The `==` comparison: like `is`, except lists are equal when their items are.
+    pub fn is_deep_equal(a: &Value, b: &Value) -> bool {
+        Self::deep_equal(a, b, &mut vec![])
+    }
+
+    // Lists that contain themselves would recurse forever, so a pair of lists that is already
+    // being compared further up is taken as equal. Any difference is still found elsewhere.
+    fn deep_equal(a: &Value, b: &Value, comparing: &mut Vec<(usize, usize)>) -> bool {
+        let (x, y) = match (a, b) {
+            (Value::List(x), Value::List(y)) => (x, y),
+            _ => return Self::is_equal(a.clone(), b.clone()),
+        };
+        if x.ptr_eq(y) {
+            return true;
+        }
+        let pair = (x.as_ptr() as usize, y.as_ptr() as usize);
+        if comparing.contains(&pair) {
+            return true;
+        }
+
+        let (x, y) = (x.borrow(), y.borrow());
+        if x.len() != y.len() {
+            return false;
+        }
+        comparing.push(pair);
+        let equal = x
+            .iter()
+            .zip(y.iter())
+            .all(|(x, y)| Self::deep_equal(x, y, comparing));
+        comparing.pop();
+        equal
+    }
+
     fn eval_binary(&mut self, expr: &BinaryExpression) -> Result<Value> {
         let left = self.evaluate(&expr.left)?;
         let right = self.evaluate(&expr.right)?;
@@ -145,6 +300,7 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Minus binary operator can only be used on numbers.",
                         )
                     }
@@ -154,6 +310,7 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Minus binary operator can only be used on numbers.",
                         )
                     }
@@ -166,6 +323,7 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Divide binary operator can only be used on numbers.",
                         )
                     }
@@ -175,72 +333,90 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Divide binary operator can only be used on numbers.",
                         )
                     }
                 };
                 Value::Number(left_val / right_val)
             }
-            TokenType::Multiply => {
-                let left_val = match left {
-                    Value::Number(x) => x,
-                    _ => {
-                        return Self::error(
-                            expr.operator.clone(),
-                            "Multiply binary operator can only be used on numbers.",
-                        )
-                    }
-                };
-                let right_val = match right {
-                    Value::Number(x) => x,
-                    _ => {
+            TokenType::Power => match (left, right) {
+                // powi is faster and exact for small whole exponents.
+                (Value::Number(x), Value::Number(y))
+                    if y.fract() == 0.0 && y.abs() <= i32::MAX as f64 =>
+                {
+                    Value::Number(x.powi(y as i32))
+                }
+                (Value::Number(x), Value::Number(y)) => Value::Number(x.powf(y)),
+                _ => {
+                    return Self::error(
+                        expr.operator.clone(),
+                        RuntimeErrorKind::TypeError,
+                        "Power binary operator can only be used on numbers.",
+                    )
+                }
+            },
+            TokenType::Multiply => match (left, right) {
+                (Value::Number(x), Value::Number(y)) => Value::Number(x * y),
+                (Value::String(x), Value::Number(y)) | (Value::Number(y), Value::String(x)) => {
+                    if y < 0.0 || y.fract() != 0.0 {
                         return Self::error(
                             expr.operator.clone(),
-                            "Multiply binary operator can only be used on numbers.",
-                        )
-                    }
-                };
-                Value::Number(left_val * right_val)
-            }
-            TokenType::Plus => {
-                if let Value::String(x) = left {
-                    if let Value::String(y) = right {
-                        Value::String(x + &y)
-                    } else {
-                        match right {
-                            Value::Number(y) => Value::String(x + &y.to_string()),
-                            Value::Boolean(y) => Value::String(x + &y.to_string()),
-                            Value::None => Value::String(x + "none"),
-                            _ => {
-                                return Self::error(
-                                    expr.operator.clone(),
-                                    "Unknown right operand in string concat.",
-                                )
-                            }
-                        }
+                            RuntimeErrorKind::TypeError,
+                            format!("Can't repeat a string {y} times, expected a non-negative whole number."),
+                        );
                     }
-                } else if let Value::Number(x) = left {
-                    if let Value::Number(y) = right {
-                        Value::Number(x + y)
-                    } else {
+                    // Checked before repeat, which aborts when the length overflows.
+                    let too_long = !x.is_empty()
+                        && (y > usize::MAX as f64
+                            || x.len()
+                                .checked_mul(y as usize)
+                                .is_none_or(|len| len > MAX_REPEAT_LEN));
+                    if too_long {
                         return Self::error(
                             expr.operator.clone(),
-                            "Cannot add non-number to number.",
+                            RuntimeErrorKind::TypeError,
+                            format!("Can't repeat a string {y} times, the result would be longer than {MAX_REPEAT_LEN} bytes."),
                         );
                     }
-                } else {
+                    Value::String(x.repeat(y as usize))
+                }
+                _ => {
                     return Self::error(
                         expr.operator.clone(),
+                        RuntimeErrorKind::TypeError,
+                        "Multiply binary operator can only be used on numbers, or a string and a number.",
+                    )
+                }
+            },
+            TokenType::Plus => match (left, right) {
+                (Value::Number(x), Value::Number(y)) => Value::Number(x + y),
+                (Value::String(x), Value::String(y)) => Value::String(x + &y),
+                // A string on either side turns the other operand into its printed form.
+                (Value::String(x), y) => Value::String(format!("{x}{y}")),
+                (x, Value::String(y)) => Value::String(format!("{x}{y}")),
+                (Value::Number(_), _) => {
+                    return Self::error(
+                        expr.operator.clone(),
+                        RuntimeErrorKind::TypeError,
+                        "Cannot add non-number to number.",
+                    )
+                }
+                _ => {
+                    return Self::error(
+                        expr.operator.clone(),
+                        RuntimeErrorKind::TypeError,
                         "Plus binary operator can only be used with strings or numbers",
-                    );
+                    )
                 }
-            }
+            },
             TokenType::Greater => {
                 let left_val = match left {
                     Value::Number(x) => x,
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Greater binary operator can only be used on numbers.",
                         )
                     }
@@ -250,6 +426,7 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Greater binary operator can only be used on numbers.",
                         )
                     }
@@ -262,6 +439,7 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Greater-or-Equal binary operator can only be used on numbers.",
                         )
                     }
@@ -271,6 +449,7 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Greater-or-Equal binary operator can only be used on numbers.",
                         )
                     }
@@ -283,6 +462,7 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Less binary operator can only be used on numbers.",
                         )
                     }
@@ -292,6 +472,7 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Less binary operator can only be used on numbers.",
                         )
                     }
@@ -304,6 +485,7 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Less-or-Equal binary operator can only be used on numbers.",
                         )
                     }
@@ -313,16 +495,41 @@ impl Interpreter {
                     _ => {
                         return Self::error(
                             expr.operator.clone(),
+                            RuntimeErrorKind::TypeError,
                             "Less-or-Equal binary operator can only be used on numbers.",
                         )
                     }
                 };
                 Value::Boolean(left_val <= right_val)
             }
+            TokenType::EqualEqual => Value::Boolean(Self::is_deep_equal(&left, &right)),
+            TokenType::BangEqual => Value::Boolean(!Self::is_deep_equal(&left, &right)),
             TokenType::Is => Value::Boolean(Self::is_equal(left, right)),
+            TokenType::IsNot => Value::Boolean(!Self::is_equal(left, right)),
+            TokenType::In => match (&left, &right) {
+                (_, Value::List(x)) => {
+                    Value::Boolean(x.borrow().iter().any(|x| Self::is_deep_equal(&left, x)))
+                }
+                (Value::String(x), Value::String(y)) => Value::Boolean(y.contains(x.as_str())),
+                (_, Value::String(_)) => {
+                    return Self::error(
+                        expr.operator.clone(),
+                        RuntimeErrorKind::TypeError,
+                        format!("Only strings can be searched for in a string, but got '{left}'."),
+                    )
+                }
+                _ => {
+                    return Self::error(
+                        expr.operator.clone(),
+                        RuntimeErrorKind::TypeError,
+                        format!("Can't search in '{right}', expected a list or string."),
+                    )
+                }
+            },
             _ => {
                 return Self::error(
                     expr.operator.clone(),
+                    RuntimeErrorKind::TypeError,
                     "Unknown operator in binary expression.",
                 )
             }
@@ -349,6 +556,13 @@ impl Interpreter {
 
     fn eval_logical(&mut self, expr: &LogicalExpression) -> Result<Value> {
         let left = self.evaluate(&expr.left)?;
+        // Both sides are always needed, so xor doesn't short-circuit.
+        if expr.operator.token_type == TokenType::Xor {
+            let right = self.evaluate(&expr.right)?;
+            return Ok(Value::Boolean(
+                Self::is_truthy(&left) != Self::is_truthy(&right),
+            ));
+        }
         if expr.operator.token_type == TokenType::Or {
             if Self::is_truthy(&left) {
                 return Ok(left);
@@ -367,34 +581,86 @@ impl Interpreter {
         for arg in &expr.args {
             args.push(self.evaluate(arg)?);
         }
+        self.call_value(callee, args, &expr.paren)
+    }
 
+    /// Calls a global function from Rust, as if the script had called it by name.
+    pub fn call_global(&mut self, name: &str, args: Vec<Value>) -> Result<Value> {
+        let token = Token::new(
+            TokenType::Identifier,
+            name.to_owned(),
+            Value::None,
+            self.line,
+        );
+        let callee = self.globals.borrow().get(&token)?;
+        self.call_value(callee, args, &token)
+    }
+
+    // `paren` is the token errors are reported at.
+    fn call_value(&mut self, callee: Value, args: Vec<Value>, paren: &Token) -> Result<Value> {
         // Wrapping an Rc in a Box feels a little cursed.
-        let mut callable: Box<dyn Callable> = match callee {
+        let callable: Box<dyn Callable> = match callee {
             Value::NativeFunction(x) => Box::new(x),
             Value::Function(x) => Box::new(x),
             Value::Class(x) => Box::new(SharedPtr::new(x)),
-            _ => return Self::error(expr.paren.clone(), "Expected callable object."),
+            _ => {
+                return Self::error(
+                    paren.clone(),
+                    RuntimeErrorKind::NotCallable,
+                    "Expected callable object.",
+                )
+            }
         };
 
         let arg_num = args.len();
         let arg_needed = callable.get_arity();
-        if arg_num != arg_needed {
+        if callable.is_variadic() {
+            if arg_num < arg_needed {
+                return Self::error(
+                    paren.clone(),
+                    RuntimeErrorKind::ArityMismatch,
+                    format!(
+                        "Expected at least {} arguments, but got {}",
+                        arg_needed, arg_num
+                    ),
+                );
+            }
+            if let Some(max) = callable.get_max_arity().filter(|max| arg_num > *max) {
+                return Self::error(
+                    paren.clone(),
+                    RuntimeErrorKind::ArityMismatch,
+                    format!("Expected at most {} arguments, but got {}", max, arg_num),
+                );
+            }
+        } else if arg_num != arg_needed {
+            return Self::error(
+                paren.clone(),
+                RuntimeErrorKind::ArityMismatch,
+                format!("Expected {} arguments, but got {}", arg_needed, arg_num),
+            );
+        }
+        if self.call_stack.len() >= MAX_CALL_DEPTH {
             return Self::error(
-                expr.paren.clone(),
-                format!("Exptected {} arguments, but got {}", arg_needed, arg_num),
+                paren.clone(),
+                RuntimeErrorKind::StackOverflow,
+                format!("Stack overflow, calls can only be nested {MAX_CALL_DEPTH} deep."),
             );
         }
-        match callable.call(self, args) {
+        self.call_stack.push((callable.get_name(), paren.line));
+        let result = callable.call(self, args);
+        self.call_stack.pop();
+        match result {
             CallableResult::Ok(x) => Ok(x),
             CallableResult::Err(e) => match e {
-                RuntimeOrNativeError::Runtime(e) => Err(e),
+                RuntimeOrNativeError::Runtime(e) => {
+                    Err(e.with_call_frame(callable.get_name(), paren.line))
+                }
+                // Natives don't know where they were called from, so their errors get the call's line.
                 RuntimeOrNativeError::Native(e) => {
-                    get_err_handler().report_native(
-                        callable.get_name().as_ref(),
-                        e,
-                        expr.paren.line,
-                    );
-                    Ok(Value::None)
+                    Err(
+                        RuntimeError::new(paren.clone(), RuntimeErrorKind::Native, e)
+                            .with_call_frame(callable.get_name(), paren.line),
+                    )
                 }
             },
         }
@@ -404,7 +670,11 @@ impl Interpreter {
         let object = self.evaluate(&expr.object)?;
         match object {
             Value::Instance(x) => x.borrow().get(&expr.name),
-            _ => Self::error(expr.name.clone(), "Only instances have properties."),
+            _ => Self::error(
+                expr.name.clone(),
+                RuntimeErrorKind::TypeError,
+                "Only instances have properties.",
+            ),
         }
     }
 
@@ -416,7 +686,11 @@ impl Interpreter {
                 x.borrow_mut().set(&expr.name, value.clone());
                 Ok(value)
             }
-            _ => Self::error(expr.name.clone(), "Only instances have fields."),
+            _ => Self::error(
+                expr.name.clone(),
+                RuntimeErrorKind::TypeError,
+                "Only instances have fields.",
+            ),
         }
     }
 
@@ -444,18 +718,58 @@ impl Interpreter {
     }
 
     fn execute_var_statement(&mut self, statement: &VarStatement) -> Result<MaybeReturn> {
-        let mut value = Value::None;
-        if let Some(init) = &statement.initializer {
-            value = self.evaluate(&init)?;
+        if let Some(unpack) = &statement.unpack {
+            return self.execute_unpack(statement, unpack);
+        }
+        for declaration in &statement.declarations {
+            let mut value = Value::None;
+            if let Some(init) = &declaration.initializer {
+                value = self.evaluate(&init)?;
+            }
+            self.env
+                .borrow_mut()
+                .define(declaration.name.lexeme.clone(), value);
+        }
+        Ok(().into())
+    }
+
+    fn execute_unpack(
+        &mut self,
+        statement: &VarStatement,
+        unpack: &Expression,
+    ) -> Result<MaybeReturn> {
+        let name = &statement.declarations[0].name;
+        let values = match self.evaluate(unpack)? {
+            Value::List(x) => x.borrow().clone(),
+            x => {
+                return Self::error(
+                    name.clone(),
+                    RuntimeErrorKind::TypeError,
+                    format!("Only lists can be unpacked, but got '{x}'."),
+                )
+            }
+        };
+        if values.len() != statement.declarations.len() {
+            return Self::error(
+                name.clone(),
+                RuntimeErrorKind::TypeError,
+                format!(
+                    "Expected {} values to unpack, but got {}.",
+                    statement.declarations.len(),
+                    values.len()
+                ),
+            );
+        }
+        for (declaration, value) in statement.declarations.iter().zip(values) {
+            self.env
+                .borrow_mut()
+                .define(declaration.name.lexeme.clone(), value);
         }
-        self.env
-            .borrow_mut()
-            .define(statement.name.lexeme.clone(), value);
         Ok(().into())
     }
 
     fn execute_function_statement(&mut self, statement: &FunctionStatement) -> Result<MaybeReturn> {
-        let function = Function::new(statement.clone(), self.env.borrow().clone());
+        let function = Function::new(Rc::new(statement.clone()), self.env.clone());
         self.env.borrow_mut().define(
             statement.name.lexeme.clone(),
             Value::Function(SharedPtr::new(function)),
@@ -464,15 +778,20 @@ impl Interpreter {
     }
 
     pub fn execute_block(&mut self, statements: &[Statement], env: EnvPtr) -> Result<MaybeReturn> {
+        // Charged here too so empty loop bodies still use up fuel.
+        self.consume_fuel()?;
         let previous = self.env.clone();
         self.env = env;
+        let mut result = Ok(().into());
         for statement in statements {
-            if let MaybeReturn::Return(x) = self.execute(statement)? {
-                return Ok(MaybeReturn::Return(x));
+            result = self.execute(statement);
+            // Stop on errors and on anything that unwinds, but always restore the previous scope.
+            if !matches!(result, Ok(MaybeReturn::Normal(_))) {
+                break;
             }
         }
         self.env = previous;
-        Ok(().into())
+        result
     }
 
     fn execute_block_statement(&mut self, statement: &BlockStatement) -> Result<MaybeReturn> {
@@ -492,25 +811,146 @@ impl Interpreter {
         }
     }
 
+    // Unlabeled jumps target the innermost loop, labeled ones only the loop with that label.
+    fn targets_loop(label: &Option<String>, loop_label: &Option<Token>) -> bool {
+        match label {
+            Some(x) => loop_label.as_ref().is_some_and(|y| &y.lexeme == x),
+            None => true,
+        }
+    }
+
     fn execute_while_statement(&mut self, statement: &WhileStatement) -> Result<MaybeReturn> {
         while Self::is_truthy(&self.evaluate(&statement.condition)?) {
             match self.execute_block_statement(&statement.body)? {
-                MaybeReturn::Return(x) => return Ok(MaybeReturn::Return(x)),
-                _ => (),
+                MaybeReturn::Break(x) if Self::targets_loop(&x, &statement.label) => {
+                    return Ok(().into())
+                }
+                MaybeReturn::Continue(x) if Self::targets_loop(&x, &statement.label) => continue,
+                MaybeReturn::Normal(_) => (),
+                unwind => return Ok(unwind),
+            };
+        }
+        match &statement.else_branch {
+            Some(x) => self.execute_block_statement(x),
+            None => Ok(().into()),
+        }
+    }
+
+    fn execute_for_loop(&mut self, statement: &ForStatement) -> Result<MaybeReturn> {
+        if let Some(x) = &statement.initializer {
+            self.execute(x)?;
+        }
+        loop {
+            if let Some(x) = &statement.condition {
+                if !Self::is_truthy(&self.evaluate(x)?) {
+                    break;
+                }
+            }
+            match self.execute_block_statement(&statement.body)? {
+                MaybeReturn::Break(x) if Self::targets_loop(&x, &statement.label) => break,
+                // Continuing still runs the increment below.
+                MaybeReturn::Continue(x) if Self::targets_loop(&x, &statement.label) => (),
+                MaybeReturn::Normal(_) => (),
+                unwind => return Ok(unwind),
+            };
+            if let Some(x) = &statement.increment {
+                self.evaluate(x)?;
+            }
+        }
+        Ok(().into())
+    }
+
+    fn execute_for_statement(&mut self, statement: &ForStatement) -> Result<MaybeReturn> {
+        let previous = self.env.clone();
+        self.env = Rc::new(RefCell::new(Environment::new(Some(previous.clone()))));
+        let result = self.execute_for_loop(statement);
+        self.env = previous;
+        result
+    }
+
+    fn execute_for_in_statement(&mut self, statement: &ForInStatement) -> Result<MaybeReturn> {
+        let iterable = self.evaluate(&statement.iterable)?;
+        // Strings can't change while the body runs, so their chars are collected once.
+        let chars: Vec<char> = match &iterable {
+            Value::String(x) => x.chars().collect(),
+            _ => Vec::new(),
+        };
+        let mut index = 0;
+        loop {
+            // Lists are indexed on every step so changes made by the body are seen.
+            let item = match &iterable {
+                Value::List(x) => match x.borrow().get(index) {
+                    Some(x) => x.clone(),
+                    None => break,
+                },
+                Value::String(_) => match chars.get(index) {
+                    Some(x) => Value::String(x.to_string()),
+                    None => break,
+                },
+                x => {
+                    return Self::error(
+                        statement.name.clone(),
+                        RuntimeErrorKind::TypeError,
+                        format!("Can't iterate over '{x}', expected a list or string."),
+                    )
+                }
+            };
+            index += 1;
+
+            let mut env = Environment::new(Some(self.env.clone()));
+            env.define(statement.name.lexeme.clone(), item);
+            let previous = std::mem::replace(&mut self.env, env.into());
+            let result = self.execute_block_statement(&statement.body);
+            self.env = previous;
+            match result? {
+                MaybeReturn::Break(x) if Self::targets_loop(&x, &statement.label) => break,
+                MaybeReturn::Continue(x) if Self::targets_loop(&x, &statement.label) => continue,
+                MaybeReturn::Normal(_) => (),
+                unwind => return Ok(unwind),
             };
         }
         Ok(().into())
     }
 
     fn execute_return_statement(&mut self, statement: &ReturnStatement) -> Result<MaybeReturn> {
-        let value = if let Some(x) = &statement.expr {
-            self.evaluate(x)?
-        } else {
-            Value::None
+        let mut values = vec![];
+        for value in &statement.values {
+            values.push(self.evaluate(value)?);
+        }
+        let value = match values.len() {
+            0 => Value::None,
+            1 => values.pop().unwrap(),
+            _ => Value::List(SharedPtr::new(values)),
         };
         Ok(MaybeReturn::Return(value))
     }
 
+    fn execute_break_statement(&mut self, statement: &BreakStatement) -> Result<MaybeReturn> {
+        let label = statement.label.as_ref().map(|x| x.lexeme.clone());
+        Ok(MaybeReturn::Break(label))
+    }
+
+    fn execute_continue_statement(&mut self, statement: &ContinueStatement) -> Result<MaybeReturn> {
+        let label = statement.label.as_ref().map(|x| x.lexeme.clone());
+        Ok(MaybeReturn::Continue(label))
+    }
+
+    fn execute_try_statement(&mut self, statement: &TryStatement) -> Result<MaybeReturn> {
+        match self.execute_block_statement(&statement.body) {
+            Err(err) => {
+                let mut env = Environment::new(Some(self.env.clone()));
+                env.define(statement.name.lexeme.clone(), err.into_value());
+                self.execute_block(&statement.handler.statements, env.into())
+            }
+            result => result,
+        }
+    }
+
+    fn execute_throw_statement(&mut self, statement: &ThrowStatement) -> Result<MaybeReturn> {
+        let value = self.evaluate(&statement.expr)?;
+        Err(RuntimeError::thrown(statement.keyword.clone(), value))
+    }
+
     fn execute_class_statement(&mut self, statement: &ClassStatement) -> Result<MaybeReturn> {
         let mut env = self.env.borrow_mut();
         env.define(statement.name.lexeme.clone(), Value::None);
@@ -520,6 +960,22 @@ impl Interpreter {
     }
 
     fn execute(&mut self, statement: &Statement) -> Result<MaybeReturn> {
+        if let Some(line) = statement.get_line() {
+            self.line = line;
+        }
+        self.consume_fuel()?;
+        if self.trace {
+            self.trace_statement(statement);
+        }
+
+        // Taken out while it runs so it can look at the interpreter.
+        if let Some(mut debugger) = self.debugger.take() {
+            if let Some(line) = statement.get_line() {
+                debugger.before_statement(self, line);
+            }
+            self.debugger = Some(debugger);
+        }
+
         match statement {
             Statement::Expression(x) => self.execute_expression_statement(x),
             Statement::Var(x) => self.execute_var_statement(x),
@@ -527,7 +983,13 @@ impl Interpreter {
             Statement::Block(x) => self.execute_block_statement(x),
             Statement::If(x) => self.execute_if_statement(x),
             Statement::While(x) => self.execute_while_statement(x),
+            Statement::For(x) => self.execute_for_statement(x),
+            Statement::ForIn(x) => self.execute_for_in_statement(x),
             Statement::Return(x) => self.execute_return_statement(x),
+            Statement::Break(x) => self.execute_break_statement(x),
+            Statement::Continue(x) => self.execute_continue_statement(x),
+            Statement::Try(x) => self.execute_try_statement(x),
+            Statement::Throw(x) => self.execute_throw_statement(x),
             Statement::Class(x) => self.execute_class_statement(x),
         }
     }
@@ -535,8 +997,185 @@ impl Interpreter {
     pub fn interpret(&mut self, statements: impl Iterator<Item = Statement>) {
         for statement in statements {
             if let Err(x) = self.execute(&statement) {
+                let kind = x.kind();
                 get_err_handler().runtime_error(x);
+                // Every statement after this would fail the same way.
+                if kind == RuntimeErrorKind::ExecutionLimit {
+                    break;
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::Parser, resolver::Resolver, stdlib};
+
+    // Runs the source the way main does, stopping at the first runtime error.
+    fn run(interpreter: &mut Interpreter, source: &str) -> Result<()> {
+        stdlib::register(interpreter);
+        let parser = Parser::new(Lexer::new(source.to_owned()));
+        let mut statements = parser.collect::<Vec<Statement>>();
+        Resolver::new(interpreter).resolve(statements.iter_mut());
+        for statement in &statements {
+            interpreter.execute(statement)?;
+        }
+        Ok(())
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Result<Value> {
+        let token = Token::new(TokenType::Identifier, name.to_owned(), Value::None, 0);
+        interpreter.get_global_env().borrow().get(&token)
+    }
+
+    #[test]
+    fn runaway_recursion_is_a_stack_overflow_error() {
+        let mut interpreter = Interpreter::new();
+        let source = "fn f(n) {\n    ret f(n + 1)\n}\nf(0)\n";
+        let err = run(&mut interpreter, source).unwrap_err();
+        assert_eq!(err.kind(), RuntimeErrorKind::StackOverflow);
+    }
+
+    #[test]
+    fn nan_propagates_through_arithmetic() {
+        let mut interpreter = Interpreter::new();
+        let source = "let n = 0 / 0\nlet a = n + 1\nlet b = 2 - n\nlet c = n * 3\nlet d = 4 / n\nlet e = n ** 2\nlet f = -n\n";
+        run(&mut interpreter, source).unwrap();
+        for name in ["n", "a", "b", "c", "d", "e", "f"] {
+            let value = global(&interpreter, name).unwrap();
+            assert!(
+                matches!(value, Value::Number(x) if x.is_nan()),
+                "{name} is {value}"
+            );
+        }
+    }
+
+    #[test]
+    fn reset_forgets_globals_but_keeps_natives() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("ARGV", Value::String("args".to_owned()));
+        let source = "let x = 1\nconst K = 2\nARGV = 3\nlet p = println\nlet range = 4\n";
+        run(&mut interpreter, source).unwrap();
+        interpreter.reset();
+
+        assert!(global(&interpreter, "x").is_err());
+        assert!(global(&interpreter, "p").is_err());
+        assert!(global(&interpreter, "K").is_err());
+        assert!(!interpreter.is_global_constant("K"));
+        assert!(matches!(
+            global(&interpreter, "range"),
+            Ok(Value::NativeFunction(_))
+        ));
+        assert!(matches!(global(&interpreter, "ARGV"), Ok(Value::String(x)) if x == "args"));
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        let mut interpreter = Interpreter::new();
+        run(&mut interpreter, "let x = 2 ** 3 ** 2 == 512\n").unwrap();
+        assert!(matches!(
+            global(&interpreter, "x"),
+            Ok(Value::Boolean(true))
+        ));
+    }
+
+    #[test]
+    fn minus_and_divide_are_left_associative() {
+        let mut interpreter = Interpreter::new();
+        let source = "let a = 10 - 3 - 2 == 5\nlet b = 100 / 10 / 5 == 2\n";
+        run(&mut interpreter, source).unwrap();
+        assert!(matches!(
+            global(&interpreter, "a"),
+            Ok(Value::Boolean(true))
+        ));
+        assert!(matches!(
+            global(&interpreter, "b"),
+            Ok(Value::Boolean(true))
+        ));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let mut interpreter = Interpreter::new();
+        // Grouped as (true or false) and false this would be false.
+        run(&mut interpreter, "let x = true or false and false\n").unwrap();
+        assert!(matches!(
+            global(&interpreter, "x"),
+            Ok(Value::Boolean(true))
+        ));
+    }
+
+    #[test]
+    fn xor_truth_table() {
+        let mut interpreter = Interpreter::new();
+        let source = "let a = true xor true\nlet b = true xor false\nlet c = false xor true\nlet d = false xor false\n";
+        run(&mut interpreter, source).unwrap();
+        for (name, expected) in [("a", false), ("b", true), ("c", true), ("d", false)] {
+            let value = global(&interpreter, name).unwrap();
+            assert!(
+                matches!(value, Value::Boolean(x) if x == expected),
+                "{name} is {value}"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_string_takes_the_else_branch() {
+        let mut interpreter = Interpreter::new();
+        let source = "let x = 0\nif \"\" {\n    x = 1\n}\nelse {\n    x = 2\n}\n";
+        run(&mut interpreter, source).unwrap();
+        assert!(matches!(global(&interpreter, "x"), Ok(Value::Number(x)) if x == 2.0));
+    }
+    #[test]
+    fn break_leaves_only_the_inner_loop() {
+        let mut interpreter = Interpreter::new();
+        let source = "let outer = 0\nlet inner = 0\nwhile outer < 3 {\n    outer = outer + 1\n    while true {\n        inner = inner + 1\n        break\n    }\n}\n";
+        run(&mut interpreter, source).unwrap();
+        assert!(matches!(global(&interpreter, "outer"), Ok(Value::Number(x)) if x == 3.0));
+        assert!(matches!(global(&interpreter, "inner"), Ok(Value::Number(x)) if x == 3.0));
+    }
+    #[test]
+    fn continue_in_for_loop_still_increments() {
+        let mut interpreter = Interpreter::new();
+        // A loop that skipped the increment would run out of fuel instead of finishing.
+        interpreter.set_fuel(Some(10_000));
+        let source = "let n = 0\nfor let i = 0, i < 5, i = i + 1 {\n    if i == 2 {\n        continue\n    }\n    n = n + 1\n}\n";
+        run(&mut interpreter, source).unwrap();
+        assert!(matches!(global(&interpreter, "n"), Ok(Value::Number(x)) if x == 4.0));
+    }
+    #[test]
+    fn contains_finds_text_across_concatenated_strings() {
+        let mut interpreter = Interpreter::new();
+        let source = "let a = contains(\"foo\" + \"bar\", \"obar\")\nlet b = contains(\"foo\" + \"bar\", \"baz\")\n";
+        run(&mut interpreter, source).unwrap();
+        assert!(matches!(
+            global(&interpreter, "a"),
+            Ok(Value::Boolean(true))
+        ));
+        assert!(matches!(
+            global(&interpreter, "b"),
+            Ok(Value::Boolean(false))
+        ));
+    }
+    #[test]
+    fn plus_joins_strings_and_numbers_in_either_order() {
+        let mut interpreter = Interpreter::new();
+        let source = "let a = \"count: \" + 5 == \"count: 5\"\nlet b = 5 + \"x\"\n";
+        run(&mut interpreter, source).unwrap();
+        assert!(matches!(
+            global(&interpreter, "a"),
+            Ok(Value::Boolean(true))
+        ));
+        assert!(matches!(global(&interpreter, "b"), Ok(Value::String(x)) if x == "5x"));
+    }
+    #[test]
+    fn scripts_read_and_write_globals_set_from_rust() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("input", Value::Number(20.0));
+        interpreter.set_global("result", Value::None);
+        run(&mut interpreter, "result = input * 2 + 1\n").unwrap();
+        assert!(matches!(global(&interpreter, "result"), Ok(Value::Number(x)) if x == 41.0));
+    }
+}
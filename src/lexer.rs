@@ -11,18 +11,26 @@ static KEYWORDS: Lazy<HashMap<String, TokenType>> = Lazy::new(|| {
         "false"     => TokenType::False,
         "true"      => TokenType::True,
         "for"       => TokenType::For,
+        "in"        => TokenType::In,
         "if"        => TokenType::If,
         "none"      => TokenType::None,
         "or"        => TokenType::Or,
+        "xor"       => TokenType::Xor,
         "is"        => TokenType::Is,
         "not"       => TokenType::Not,
         "ret"       => TokenType::Ret,
         "super"     => TokenType::Super,
         "this"      => TokenType::This,
         "let"       => TokenType::Let,
+        "const"     => TokenType::Const,
         "fn"    => TokenType::Fn,
         "end"       => TokenType::End,
-        "while"     => TokenType::While
+        "while"     => TokenType::While,
+        "break"     => TokenType::Break,
+        "continue"  => TokenType::Continue,
+        "try"       => TokenType::Try,
+        "catch"     => TokenType::Catch,
+        "throw"     => TokenType::Throw
     )
 });
 
@@ -37,10 +45,15 @@ pub struct Lexer {
 
 impl Lexer {
     pub fn new(source: String) -> Self {
+        // Skip a shebang line, but leave its newline so line numbers still count it.
+        let current = match source.starts_with("#!") {
+            true => source.find('\n').unwrap_or(source.len()),
+            false => 0,
+        };
         Lexer {
             source,
-            start: 0,
-            current: 0,
+            start: current,
+            current,
             line: 0,
             ignore_newline: false,
             last_token: None,
@@ -51,8 +64,14 @@ impl Lexer {
         self.current >= self.source.len()
     }
 
+    // current is a byte offset, so decode the char at it instead of counting chars from the start.
     fn get_current_char_unchecked(&self) -> char {
-        unsafe { self.source.chars().nth(self.current).unwrap_unchecked() }
+        unsafe {
+            self.source[self.current..]
+                .chars()
+                .next()
+                .unwrap_unchecked()
+        }
     }
 
     fn peek(&self) -> char {
@@ -64,16 +83,16 @@ impl Lexer {
     }
 
     fn peekpeek(&self) -> char {
-        if self.current + 1 >= self.source.len() {
+        if self.at_end() {
             '\0'
         } else {
-            unsafe { self.source.chars().nth(self.current + 1).unwrap_unchecked() }
+            self.source[self.current..].chars().nth(1).unwrap_or('\0')
         }
     }
 
     fn next_char(&mut self) -> char {
         let ch = self.get_current_char_unchecked();
-        self.current += 1;
+        self.current += ch.len_utf8();
         ch
     }
 
@@ -159,6 +178,11 @@ impl Lexer {
             None => TokenType::Identifier,
         };
 
+        // An identifier directly followed by ':' labels the loop after it.
+        if token_type == TokenType::Identifier && self.matches_next(':') {
+            return Token::new(TokenType::Label, text, Value::None, self.line);
+        }
+
         self.make_token(token_type)
     }
 
@@ -173,12 +197,33 @@ impl Lexer {
             TokenType::String,
             TokenType::None,
             TokenType::End,
+            TokenType::Break,
+            TokenType::Continue,
             TokenType::Identifier,
         ];
         STMT_END_TOKENS.iter().any(|x| x == test_type)
     }
 
+    // Closes the last statement if it is still open, then keeps returning EOF.
+    fn lex_end(&self) -> Token {
+        match self.last_token.as_ref().map(|x| x.token_type) {
+            Some(TokenType::EOF) | Some(TokenType::StatementEnd) | None => {
+                Token::new(TokenType::EOF, "EOF".to_owned(), Value::None, self.line)
+            }
+            Some(_) => Token::new(
+                TokenType::StatementEnd,
+                "\n".to_owned(),
+                Value::None,
+                self.line,
+            ),
+        }
+    }
+
     fn lex_token(&mut self) -> Token {
+        if self.at_end() {
+            return self.lex_end();
+        }
+
         self.start = self.current;
         let next = self.next_char();
         match next {
@@ -223,7 +268,15 @@ impl Lexer {
             '{' => self.make_token(TokenType::BraceOpen),
             '}' => self.make_token(TokenType::BraceClose),
             ',' => self.make_token(TokenType::Comma),
-            '.' => self.make_token(TokenType::Dot),
+            '.' => {
+                if self.peek() == '.' && self.peekpeek() == '.' {
+                    self.next_char();
+                    self.next_char();
+                    self.make_token(TokenType::Ellipsis)
+                } else {
+                    self.make_token(TokenType::Dot)
+                }
+            }
             '-' => {
                 if self.matches_next('-') {
                     self.make_token(TokenType::MinusMinus)
@@ -242,9 +295,31 @@ impl Lexer {
                     self.make_token(TokenType::Plus)
                 }
             }
-            '*' => self.make_token(TokenType::Multiply),
+            '*' => {
+                if self.matches_next('*') {
+                    self.make_token(TokenType::Power)
+                } else {
+                    self.make_token(TokenType::Multiply)
+                }
+            }
             '/' => self.make_token(TokenType::Divide),
-            '=' => self.make_token(TokenType::Equal),
+            '=' => {
+                if self.matches_next('=') {
+                    self.make_token(TokenType::EqualEqual)
+                } else {
+                    self.make_token(TokenType::Equal)
+                }
+            }
+            '!' => {
+                if self.matches_next('=') {
+                    self.make_token(TokenType::BangEqual)
+                } else {
+                    // An error rather than a report, so the script doesn't run without the '!'.
+                    let token = self.make_token(TokenType::Not);
+                    get_err_handler().error(token, "Expected '=' after '!', use 'not' to negate.");
+                    self.lex_token()
+                }
+            }
             '<' => {
                 let token = match self.matches_next('=') {
                     true => TokenType::LessEqual,
@@ -269,7 +344,7 @@ impl Lexer {
                         Some(x) => return x,
                         None => return self.lex_token(),
                     }
-                } else if next.is_ascii_alphanumeric() {
+                } else if Self::alphanumeric_or_underscore(next) {
                     return self.handle_identifier();
                 }
                 self.lex_token()
@@ -279,26 +354,24 @@ impl Lexer {
 
     //TODO: Convert to iterator
     pub fn lex(&mut self) -> Token {
-        if self.at_end() {
-            if let Some(x) = &self.last_token {
-                let eof = Token::new(TokenType::EOF, "EOF".to_owned(), Value::None, self.line);
-                if let TokenType::EOF = x.token_type {
-                    return eof;
-                }
-                self.last_token = Some(eof);
-                return Token::new(
-                    TokenType::StatementEnd,
-                    "\n".to_owned(),
-                    Value::None,
-                    self.line,
-                );
-            }
-        }
-
         let token = self.lex_token();
         self.last_token = Some(token.clone());
         token
     }
+
+    /// Lexes the whole source, for tools that want the tokens without parsing them. The last
+    /// token is always EOF.
+    pub fn tokenize(mut self) -> Vec<Token> {
+        let mut tokens = vec![];
+        loop {
+            let token = self.lex();
+            let is_eof = token.token_type == TokenType::EOF;
+            tokens.push(token);
+            if is_eof {
+                return tokens;
+            }
+        }
+    }
 }
 
 impl Iterator for Lexer {
@@ -1,3 +1,4 @@
+mod debugger;
 mod environment;
 mod error;
 mod expression;
@@ -24,15 +25,10 @@ use std::{
     path::Path,
 };
 
-use crate::value::{NativeFunction, Value};
+use crate::value::{Callable, NativeFunction, SharedPtr, Value};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-fn get_source_path() -> Option<String> {
-    let mut args = args();
-    args.nth(1)
-}
-
 fn run(source: String, interpreter: &mut Interpreter) -> Result<()> {
     stdlib::register(interpreter);
     interpreter.register_native(NativeFunction::new("hello_world".to_owned(), 0, |_, _| {
@@ -40,9 +36,12 @@ fn run(source: String, interpreter: &mut Interpreter) -> Result<()> {
         Ok(Value::None)
     }));
 
+    get_err_handler().clear_error();
+    let source_copy = source.clone();
     let lexer = Lexer::new(source);
     let parser = Parser::new(lexer);
     let mut statements = parser.collect::<Vec<Statement>>();
+    attach_function_sources(&mut statements, &source_copy);
     let mut resolver = Resolver::new(interpreter);
     resolver.resolve(statements.iter_mut());
 
@@ -54,36 +53,253 @@ fn run(source: String, interpreter: &mut Interpreter) -> Result<()> {
     Ok(())
 }
 
-fn run_interactively() -> Result<()> {
-    let mut interpreter = Interpreter::new();
+// Copies the text of each top-level function declaration, from its first line until its
+// brackets are closed again.
+fn attach_function_sources(statements: &mut [Statement], source: &str) {
+    let lines = source.lines().collect::<Vec<_>>();
+    for statement in statements {
+        if let Statement::Function(x) = statement {
+            let mut text = String::new();
+            for line in lines.iter().skip(x.name.line) {
+                text += line;
+                text.push('\n');
+                if nesting_depth(&text) <= 0 {
+                    break;
+                }
+            }
+            x.source = Some(text.into());
+        }
+    }
+}
+
+const REPL_HELP: &str = "\
+:help         Show this message.
+:quit         Exit the REPL.
+:load <path>  Run a file in the current session.
+:call <name> [args...]
+              Call a global function, passing the arguments as strings.
+:globals      List the defined globals.
+:reset        Forget the globals defined in this session.";
+
+enum ReplAction {
+    Continue,
+    Quit,
+}
+
+fn run_command(command: &str, interpreter: &mut Interpreter) -> Result<ReplAction> {
+    let (name, arg) = match command.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (command, ""),
+    };
+    match name {
+        ":help" => println!("{REPL_HELP}"),
+        ":quit" => return Ok(ReplAction::Quit),
+        ":load" => {
+            if arg.is_empty() {
+                println!("Usage: :load <path>");
+            } else {
+                load_file(arg, interpreter)?;
+            }
+        }
+        ":call" => {
+            let mut words = arg.split_whitespace();
+            match words.next() {
+                Some(func_name) => {
+                    let args = words.map(|x| Value::String(x.to_owned())).collect();
+                    match interpreter.call_global(func_name, args) {
+                        Ok(x) => println!("{x}"),
+                        Err(err) => get_err_handler().runtime_error(err),
+                    }
+                }
+                None => println!("Usage: :call <name> [args...]"),
+            }
+        }
+        ":globals" => {
+            let globals = interpreter.get_global_env();
+            for (name, value) in globals.borrow().sorted_entries() {
+                println!("{name} = {value}");
+            }
+        }
+        ":reset" => interpreter.reset(),
+        _ => println!("Unknown command '{name}'. Type :help for a list of commands."),
+    }
+    Ok(ReplAction::Continue)
+}
+
+// Counts unclosed brackets, ignoring those inside strings and comments.
+fn nesting_depth(source: &str) -> i32 {
+    let mut depth = 0;
+    let mut chars = source.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            '"' => {
+                chars.find(|x| *x == '"');
+            }
+            '?' => {
+                chars.find(|x| *x == '\n');
+            }
+            _ => (),
+        }
+    }
+    depth
+}
+
+fn run_interactively(interpreter: &mut Interpreter) -> Result<()> {
     let mut stdout = stdout().lock();
     let mut stdin = stdin().lock();
     let mut strbuf = String::new();
     loop {
-        stdout.write_all(b"> ")?;
+        // Continuation lines are appended to strbuf until all brackets are closed.
+        let prompt: &[u8] = if strbuf.is_empty() { b"> " } else { b"... " };
+        stdout.write_all(prompt)?;
         stdout.flush()?;
         let count = stdin.read_line(&mut strbuf)?;
         if count == 0 {
             break;
         }
-        run(strbuf.clone(), &mut interpreter).ok();
+        let line = strbuf.trim();
+        if line.is_empty() {
+            strbuf.clear();
+            continue;
+        }
+        if line.starts_with(':') {
+            match run_command(line, interpreter) {
+                Ok(ReplAction::Quit) => break,
+                Ok(ReplAction::Continue) => (),
+                Err(err) => eprintln!("{err}"),
+            }
+        } else if nesting_depth(&strbuf) > 0 {
+            continue;
+        } else {
+            run(strbuf.clone(), interpreter).ok();
+        }
         strbuf.clear();
     }
     Ok(())
 }
 
-fn run_file(path: impl AsRef<Path>) -> Result<()> {
+fn load_file(path: impl AsRef<Path>, interpreter: &mut Interpreter) -> Result<()> {
     let path = canonicalize(path)?;
-    let mut interpreter = Interpreter::new();
     let mut buf = String::new();
     File::open(path)?.read_to_string(&mut buf)?;
-    run(buf, &mut interpreter)?;
+    run(buf, interpreter)
+}
+
+fn dump_tokens(path: impl AsRef<Path>) -> Result<()> {
+    let mut buf = String::new();
+    File::open(path)?.read_to_string(&mut buf)?;
+    for token in Lexer::new(buf).tokenize() {
+        // Debug formatting keeps newline lexemes on one line.
+        println!(
+            "{:>4} {:?} {:?}",
+            token.line + 1,
+            token.token_type,
+            token.lexeme
+        );
+    }
+    Ok(())
+}
+
+fn run_file(path: impl AsRef<Path>, interpreter: &mut Interpreter) -> Result<()> {
+    load_file(path, interpreter)
+}
+
+// Globals with a literal form and functions declared at the top level survive, other objects
+// are skipped.
+fn save_session(path: impl AsRef<Path>, interpreter: &Interpreter) -> Result<()> {
+    let mut file = File::create(path)?;
+    let globals = interpreter.get_global_env();
+    for (name, value) in globals.borrow().sorted_entries() {
+        // ARGV comes from the command line of each run.
+        if name == "ARGV" {
+            continue;
+        }
+        let literal = match value {
+            // The lexer can't read exponents, so numbers that display as 1e21 are written out.
+            Value::Number(x) if x.is_finite() => match value.to_string() {
+                text if text.contains('e') => x.to_string(),
+                text => text,
+            },
+            Value::Boolean(x) => x.to_string(),
+            Value::None => "none".to_owned(),
+            // Strings can't contain escaped quotes, so those can't be written back out.
+            Value::String(x) if !x.contains('"') => format!("\"{x}\""),
+            Value::NativeFunction(_) => continue,
+            // Written as the declaration itself, unless it's stored under another name.
+            Value::Function(x) if x.get_name() == *name && x.borrow().get_source().is_some() => {
+                file.write_all(x.borrow().get_source().unwrap_or_default().as_bytes())?;
+                continue;
+            }
+            _ => {
+                eprintln!("Not saving global '{name}' to the session, its value can't be stored.");
+                continue;
+            }
+        };
+        let keyword = match interpreter.is_global_constant(name) {
+            true => "const",
+            false => "let",
+        };
+        writeln!(file, "{keyword} {name} = {literal}")?;
+    }
     Ok(())
 }
 
+fn run_session(path: impl AsRef<Path>, interpreter: &mut Interpreter) -> Result<()> {
+    let path = path.as_ref();
+    if path.exists() {
+        load_file(path, interpreter)?;
+    }
+    run_interactively(interpreter)?;
+    save_session(path, interpreter)
+}
+
 fn main() -> Result<()> {
-    match get_source_path() {
-        Some(x) => run_file(x),
-        None => run_interactively(),
+    let mut interpreter = Interpreter::new();
+    let mut session = None;
+    let mut path = None;
+    let mut tokens = false;
+    let mut args = args().skip(1);
+    // Options come first, the first other argument is the script to run.
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--session" => match args.next() {
+                Some(x) => session = Some(x),
+                None => return Err("Expected a session file path after --session.".into()),
+            },
+            "--strict" => interpreter.set_strict(true),
+            "--warn-unused" => interpreter.set_warn_unused(true),
+            "--debug" => interpreter.enable_debugger(),
+            "--trace" => interpreter.set_trace(true),
+            "--tokens" => tokens = true,
+            "--fuel" => match args.next().map(|x| x.parse::<u64>()) {
+                Some(Ok(x)) => interpreter.set_fuel(Some(x)),
+                _ => return Err("Expected a statement count after --fuel.".into()),
+            },
+            x if x.starts_with("--") => return Err(format!("Unknown option '{x}'.").into()),
+            _ => {
+                path = Some(arg);
+                break;
+            }
+        }
+    }
+
+    // Whatever follows the script path is passed on to the script.
+    let argv = args.map(Value::String).collect();
+    interpreter.set_global("ARGV", Value::List(SharedPtr::new(argv)));
+
+    if tokens {
+        return match path {
+            Some(x) => dump_tokens(x),
+            None => Err("Expected a script path after --tokens.".into()),
+        };
+    }
+
+    match (session, path) {
+        (Some(_), Some(_)) => Err("Can't run a file and a session at the same time.".into()),
+        (Some(x), None) => run_session(x, &mut interpreter),
+        (None, Some(x)) => run_file(x, &mut interpreter),
+        (None, None) => run_interactively(&mut interpreter),
     }
 }
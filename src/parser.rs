@@ -2,15 +2,16 @@ use core::panic;
 use std::iter::Peekable;
 
 use crate::{
-    error::{get_err_handler, Result, RuntimeError},
+    error::{get_err_handler, Result, RuntimeError, RuntimeErrorKind},
     expression::{
         AssignExpression, BinaryExpression, CallExpression, Expression, GetExpression,
         GroupingExpression, LiteralExpression, LogicalExpression, SetExpression, UnaryExpression,
         VariableExpression,
     },
     statement::{
-        BlockStatement, ClassStatement, ExpressionStatement, FunctionStatement, IfStatement,
-        ReturnStatement, Statement, VarStatement, WhileStatement,
+        BlockStatement, BreakStatement, ClassStatement, ContinueStatement, ExpressionStatement,
+        ForInStatement, ForStatement, FunctionStatement, IfStatement, ReturnStatement, Statement,
+        ThrowStatement, TryStatement, VarDeclaration, VarStatement, WhileStatement,
     },
     token::{Token, TokenType},
     value::{FunctionKind, Value},
@@ -21,6 +22,8 @@ const MAX_FUNC_ARG_COUNT: usize = 255;
 pub struct Parser<I: Iterator<Item = Token>> {
     tokens: Peekable<I>,
     last_token: Option<Token>,
+    // Off while parsing a for loop's initializer, where 'in' starts a for-in loop instead.
+    allow_in: bool,
 }
 
 impl<I: Iterator<Item = Token>> Parser<I> {
@@ -28,12 +31,17 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         Self {
             tokens: tokens.peekable(),
             last_token: None,
+            allow_in: true,
         }
     }
 
     fn error<T>(token: &Token, msg: &str) -> Result<T> {
         get_err_handler().report(token.line, msg);
-        Err(RuntimeError::new(token.clone(), msg))
+        Err(RuntimeError::new(
+            token.clone(),
+            RuntimeErrorKind::Syntax,
+            msg,
+        ))
     }
 
     fn check(&mut self, typ: TokenType) -> bool {
@@ -198,7 +206,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn handle_unary(&mut self) -> Result<Expression> {
-        if self.match_next_token(&[TokenType::Not, TokenType::Minus]) {
+        if self.match_next_token(&[TokenType::Not, TokenType::Minus, TokenType::Plus]) {
             let operator = self.previous();
             let right = self.handle_unary()?;
             return Ok(Expression::Unary(Box::new(UnaryExpression {
@@ -206,19 +214,69 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                 right,
             })));
         }
-        self.handle_call()
+        self.handle_power()
     }
 
-    fn handle_factor(&mut self) -> Result<Expression> {
-        let mut expr = self.handle_unary()?;
-        while self.match_next_token(&[TokenType::Divide, TokenType::Multiply]) {
+    // Binds tighter than unary operators on its left, so -2 ** 2 is -4. The exponent is parsed
+    // with handle_unary, which makes the operator right-associative: 2 ** 3 ** 2 is 2 ** 9.
+    fn handle_power(&mut self) -> Result<Expression> {
+        let expr = self.handle_call()?;
+        if self.match_next_token(&[TokenType::Power]) {
             let operator = self.previous();
             let right = self.handle_unary()?;
-            expr = Expression::Binary(Box::new(BinaryExpression {
+            return Ok(Expression::Binary(Box::new(BinaryExpression {
                 left: expr,
                 operator,
                 right,
-            }));
+            })));
+        }
+        Ok(expr)
+    }
+
+    fn as_number_literal(expr: &Expression) -> Option<f64> {
+        match expr {
+            Expression::Literal(x) => match x.value {
+                Value::Number(x) => Some(x),
+                _ => None,
+            },
+            Expression::Grouping(x) => Self::as_number_literal(&x.expr),
+            _ => None,
+        }
+    }
+
+    // Computes arithmetic on two number literals at parse time instead of on every evaluation.
+    fn fold_arithmetic(left: Expression, operator: Token, right: Expression) -> Expression {
+        if let (Some(x), Some(y)) = (
+            Self::as_number_literal(&left),
+            Self::as_number_literal(&right),
+        ) {
+            let folded = match operator.token_type {
+                TokenType::Plus => Some(x + y),
+                TokenType::Minus => Some(x - y),
+                TokenType::Multiply => Some(x * y),
+                // Division by zero is left for the interpreter to handle.
+                TokenType::Divide if y != 0.0 => Some(x / y),
+                _ => None,
+            };
+            if let Some(value) = folded {
+                return Expression::Literal(Box::new(LiteralExpression {
+                    value: Value::Number(value),
+                }));
+            }
+        }
+        Expression::Binary(Box::new(BinaryExpression {
+            left,
+            operator,
+            right,
+        }))
+    }
+
+    fn handle_factor(&mut self) -> Result<Expression> {
+        let mut expr = self.handle_unary()?;
+        while self.match_next_token(&[TokenType::Divide, TokenType::Multiply]) {
+            let operator = self.previous();
+            let right = self.handle_unary()?;
+            expr = Self::fold_arithmetic(expr, operator, right);
         }
         Ok(expr)
     }
@@ -228,11 +286,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         while self.match_next_token(&[TokenType::Minus, TokenType::Plus]) {
             let operator = self.previous();
             let right = self.handle_factor()?;
-            expr = Expression::Binary(Box::new(BinaryExpression {
-                left: expr,
-                operator,
-                right,
-            }));
+            expr = Self::fold_arithmetic(expr, operator, right);
         }
         Ok(expr)
     }
@@ -244,7 +298,8 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             TokenType::GreaterEqual,
             TokenType::Less,
             TokenType::LessEqual,
-        ]) {
+        ]) || (self.allow_in && self.match_next_token(&[TokenType::In]))
+        {
             let operator = self.previous();
             let right = self.handle_term()?;
             expr = Expression::Binary(Box::new(BinaryExpression {
@@ -258,12 +313,20 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
     fn handle_equality(&mut self) -> Result<Expression> {
         let mut expr = self.handle_comparison()?;
-        while self.match_next_token(&[TokenType::Is, TokenType::Not]) {
-            let operator = self.previous();
+        while self.match_next_token(&[TokenType::Is, TokenType::EqualEqual, TokenType::BangEqual]) {
+            let mut operator = self.previous();
+            // 'is not' negates 'is', rather than comparing to the negated operand.
+            if operator.token_type == TokenType::Is && self.match_next_token(&[TokenType::Not]) {
+                operator = Token {
+                    token_type: TokenType::IsNot,
+                    lexeme: "is not".to_owned(),
+                    ..operator
+                };
+            }
             let right = self.handle_comparison()?;
             expr = Expression::Binary(Box::new(BinaryExpression {
                 left: expr,
-                operator: operator.clone(),
+                operator,
                 right,
             }));
         }
@@ -284,9 +347,9 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         Ok(expr)
     }
 
-    fn handle_or(&mut self) -> Result<Expression> {
+    fn handle_xor(&mut self) -> Result<Expression> {
         let mut expr = self.handle_and()?;
-        while self.match_next_token(&[TokenType::And]) {
+        while self.match_next_token(&[TokenType::Xor]) {
             let operator = self.previous();
             let right = self.handle_and()?;
             expr = Expression::Logical(Box::new(LogicalExpression {
@@ -298,6 +361,20 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         Ok(expr)
     }
 
+    fn handle_or(&mut self) -> Result<Expression> {
+        let mut expr = self.handle_xor()?;
+        while self.match_next_token(&[TokenType::Or]) {
+            let operator = self.previous();
+            let right = self.handle_xor()?;
+            expr = Expression::Logical(Box::new(LogicalExpression {
+                left: expr,
+                operator,
+                right,
+            }));
+        }
+        Ok(expr)
+    }
+
     fn handle_assignment(&mut self) -> Result<Expression> {
         let expr = self.handle_or()?;
         if self.match_next_token(&[TokenType::Equal]) {
@@ -382,6 +459,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
     }
 
     fn handle_if_statement(&mut self) -> Result<Statement> {
+        let keyword = self.previous();
         let condition = self.handle_expression()?;
         let then_branch = match self.handle_statement()? {
             Statement::Block(x) => x,
@@ -397,33 +475,186 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             };
         }
         Ok(Statement::If(IfStatement {
+            keyword,
             condition,
             then_branch,
             else_branch,
         }))
     }
 
-    fn handle_while_statement(&mut self) -> Result<Statement> {
+    fn handle_while_statement(&mut self, label: Option<Token>) -> Result<Statement> {
+        let keyword = self.previous();
         let condition = self.handle_expression()?;
         let body = match self.handle_statement()? {
             Statement::Block(x) => x,
             _ => return Self::error(&self.previous(), "Expected block statement after 'while'."),
         };
-        Ok(Statement::While(WhileStatement { condition, body }))
+        let mut else_branch = None;
+        if self.match_next_token(&[TokenType::Else]) {
+            else_branch = match self.handle_statement()? {
+                Statement::Block(x) => Some(x),
+                _ => {
+                    return Self::error(&self.previous(), "Expected a block statement after else.")
+                }
+            };
+        }
+        Ok(Statement::While(WhileStatement {
+            keyword,
+            label,
+            condition,
+            body,
+            else_branch,
+        }))
     }
 
-    fn handle_return_statement(&mut self) -> Result<Statement> {
+    fn handle_for_statement(&mut self, label: Option<Token>) -> Result<Statement> {
         let keyword = self.previous();
-        let expr = if !self.check(TokenType::BraceClose) {
-            Some(self.handle_expression()?)
+        let initializer = if self.check(TokenType::Comma) {
+            None
+        } else if self.match_next_token(&[TokenType::Let, TokenType::Const]) {
+            Some(Box::new(self.parse_var_declaration(false)?))
         } else {
+            self.allow_in = false;
+            let expr = self.handle_expression();
+            self.allow_in = true;
+            let expr = expr?;
+            if let Expression::Variable(x) = &expr {
+                if self.match_next_token(&[TokenType::In]) {
+                    return self.handle_for_in_statement(label, x.name.clone());
+                }
+            }
+            Some(Box::new(Statement::Expression(ExpressionStatement {
+                expr,
+            })))
+        };
+        self.consume_if(TokenType::Comma, "Expected ',' after loop initializer.")?;
+
+        let condition = if self.check(TokenType::Comma) {
+            None
+        } else {
+            Some(self.handle_expression()?)
+        };
+        self.consume_if(TokenType::Comma, "Expected ',' after loop condition.")?;
+
+        let increment = if self.check(TokenType::BraceOpen) {
             None
+        } else {
+            Some(self.handle_expression()?)
+        };
+
+        let body = match self.handle_statement()? {
+            Statement::Block(x) => x,
+            _ => return Self::error(&self.previous(), "Expected block statement after 'for'."),
         };
+        Ok(Statement::For(ForStatement {
+            keyword,
+            label,
+            initializer,
+            condition,
+            increment,
+            body,
+        }))
+    }
+
+    fn handle_for_in_statement(&mut self, label: Option<Token>, name: Token) -> Result<Statement> {
+        let iterable = self.handle_expression()?;
+        let body = match self.handle_statement()? {
+            Statement::Block(x) => x,
+            _ => return Self::error(&self.previous(), "Expected block statement after 'for'."),
+        };
+        Ok(Statement::ForIn(ForInStatement {
+            label,
+            name,
+            iterable,
+            body,
+        }))
+    }
+
+    fn handle_labeled_statement(&mut self) -> Result<Statement> {
+        let label = self.previous();
+        if self.match_next_token(&[TokenType::While]) {
+            self.handle_while_statement(Some(label))
+        } else if self.match_next_token(&[TokenType::For]) {
+            self.handle_for_statement(Some(label))
+        } else {
+            Self::error(&label, "Expected a loop after label.")
+        }
+    }
+
+    fn handle_return_statement(&mut self) -> Result<Statement> {
+        let keyword = self.previous();
+        let mut values = vec![];
+        if !self.check(TokenType::BraceClose) {
+            values.push(self.handle_expression()?);
+            while self.match_next_token(&[TokenType::Comma]) {
+                values.push(self.handle_expression()?);
+            }
+        }
         self.consume_if(
             TokenType::StatementEnd,
             "Expected newline after return value.",
         )?;
-        Ok(Statement::Return(ReturnStatement { expr, keyword }))
+        Ok(Statement::Return(ReturnStatement { values, keyword }))
+    }
+
+    fn handle_try_statement(&mut self) -> Result<Statement> {
+        let keyword = self.previous();
+        let body = match self.handle_statement()? {
+            Statement::Block(x) => x,
+            _ => return Self::error(&self.previous(), "Expected block statement after 'try'."),
+        };
+        self.consume_if(TokenType::Catch, "Expected 'catch' after try block.")?;
+        // Parentheses around the error name are optional, like around any other condition.
+        let parenthesized = self.match_next_token(&[TokenType::ParenOpen]);
+        let name = self.consume_if(TokenType::Identifier, "Expected error name after 'catch'.")?;
+        if parenthesized {
+            self.consume_if(TokenType::ParenClose, "Expected ')' after error name.")?;
+        }
+        let handler = match self.handle_statement()? {
+            Statement::Block(x) => x,
+            _ => return Self::error(&self.previous(), "Expected block statement after 'catch'."),
+        };
+        Ok(Statement::Try(TryStatement {
+            keyword,
+            body,
+            name,
+            handler,
+        }))
+    }
+
+    fn handle_throw_statement(&mut self) -> Result<Statement> {
+        let keyword = self.previous();
+        let expr = self.handle_expression()?;
+        self.consume_if(
+            TokenType::StatementEnd,
+            "Expected newline after thrown value.",
+        )?;
+        Ok(Statement::Throw(ThrowStatement { keyword, expr }))
+    }
+
+    fn consume_loop_label(&mut self) -> Option<Token> {
+        if self.match_next_token(&[TokenType::Identifier]) {
+            Some(self.previous())
+        } else {
+            None
+        }
+    }
+
+    fn handle_break_statement(&mut self) -> Result<Statement> {
+        let keyword = self.previous();
+        let label = self.consume_loop_label();
+        self.consume_if(TokenType::StatementEnd, "Expected newline after 'break'.")?;
+        Ok(Statement::Break(BreakStatement { keyword, label }))
+    }
+
+    fn handle_continue_statement(&mut self) -> Result<Statement> {
+        let keyword = self.previous();
+        let label = self.consume_loop_label();
+        self.consume_if(
+            TokenType::StatementEnd,
+            "Expected newline after 'continue'.",
+        )?;
+        Ok(Statement::Continue(ContinueStatement { keyword, label }))
     }
 
     fn handle_statement(&mut self) -> Result<Statement> {
@@ -432,25 +663,69 @@ impl<I: Iterator<Item = Token>> Parser<I> {
         } else if self.match_next_token(&[TokenType::If]) {
             self.handle_if_statement()
         } else if self.match_next_token(&[TokenType::While]) {
-            self.handle_while_statement()
+            self.handle_while_statement(None)
+        } else if self.match_next_token(&[TokenType::For]) {
+            self.handle_for_statement(None)
+        } else if self.match_next_token(&[TokenType::Label]) {
+            self.handle_labeled_statement()
         } else if self.match_next_token(&[TokenType::Ret]) {
             self.handle_return_statement()
+        } else if self.match_next_token(&[TokenType::Break]) {
+            self.handle_break_statement()
+        } else if self.match_next_token(&[TokenType::Continue]) {
+            self.handle_continue_statement()
+        } else if self.match_next_token(&[TokenType::Try]) {
+            self.handle_try_statement()
+        } else if self.match_next_token(&[TokenType::Throw]) {
+            self.handle_throw_statement()
         } else {
             self.handle_expression_statement()
         }
     }
 
-    fn handle_var_declaration(&mut self) -> Result<Statement> {
+    fn parse_single_var_declaration(&mut self) -> Result<VarDeclaration> {
         let name = self.consume_if(TokenType::Identifier, "Expected variable name.")?;
         let mut initializer = None;
         if self.match_next_token(&[TokenType::Equal]) {
             initializer = Some(self.handle_expression()?);
         }
+        Ok(VarDeclaration { name, initializer })
+    }
+
+    // Commas separate the parts of a for loop, so only declaration statements can declare
+    // several variables.
+    fn parse_var_declaration(&mut self, allow_multiple: bool) -> Result<Statement> {
+        let constant = self.previous().token_type == TokenType::Const;
+        let mut declarations = vec![self.parse_single_var_declaration()?];
+        while allow_multiple && self.match_next_token(&[TokenType::Comma]) {
+            declarations.push(self.parse_single_var_declaration()?);
+        }
+        // When only the last of several names has a value, as in `let x, y = f()`, the value is
+        // unpacked into all of them.
+        let mut unpack = None;
+        let (last, rest) = declarations.split_last_mut().unwrap();
+        if !rest.is_empty() && rest.iter().all(|x| x.initializer.is_none()) {
+            unpack = last.initializer.take();
+        }
+        if constant && unpack.is_none() {
+            if let Some(x) = declarations.iter().find(|x| x.initializer.is_none()) {
+                return Self::error(&x.name, "Expected '=' after constant name.");
+            }
+        }
+        Ok(Statement::Var(VarStatement {
+            declarations,
+            constant,
+            unpack,
+        }))
+    }
+
+    fn handle_var_declaration(&mut self) -> Result<Statement> {
+        let declaration = self.parse_var_declaration(true)?;
         self.consume_if(
             TokenType::StatementEnd,
             "Expected statement end after variable declaration.",
         )?;
-        Ok(Statement::Var(VarStatement { name, initializer }))
+        Ok(declaration)
     }
 
     fn handle_function_declaration(&mut self, kind: FunctionKind) -> Result<Statement> {
@@ -460,6 +735,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             &format!("Expected '(' after {} name.", kind),
         )?;
         let mut params = vec![];
+        let mut rest = None;
         if !self.check(TokenType::ParenClose) {
             loop {
                 if params.len() >= MAX_FUNC_ARG_COUNT {
@@ -468,6 +744,14 @@ impl<I: Iterator<Item = Token>> Parser<I> {
                         &format!("Can't have more than {} parameters.", MAX_FUNC_ARG_COUNT),
                     );
                 }
+                if self.match_next_token(&[TokenType::Ellipsis]) {
+                    // The rest parameter collects any extra arguments, so it must come last.
+                    rest = Some(self.consume_if(
+                        TokenType::Identifier,
+                        "Expected parameter name after '...'.",
+                    )?);
+                    break;
+                }
                 params.push(self.consume_if(TokenType::Identifier, "Expected paramter name.")?);
                 if !self.match_next_token(&[TokenType::Comma]) {
                     break;
@@ -484,6 +768,8 @@ impl<I: Iterator<Item = Token>> Parser<I> {
             body,
             name,
             params,
+            rest,
+            source: None,
         }))
     }
 
@@ -515,7 +801,7 @@ impl<I: Iterator<Item = Token>> Parser<I> {
 
     fn handle_declaration(&mut self) -> Result<Statement> {
         let had_err;
-        if self.match_next_token(&[TokenType::Let]) {
+        if self.match_next_token(&[TokenType::Let, TokenType::Const]) {
             match self.handle_var_declaration() {
                 Ok(x) => return Ok(x),
                 Err(_) => had_err = true,
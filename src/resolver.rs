@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     error::get_err_handler,
@@ -8,44 +8,106 @@ use crate::{
     },
     interpreter::Interpreter,
     statement::{
-        BlockStatement, ClassStatement, ExpressionStatement, FunctionStatement, IfStatement,
-        ReturnStatement, Statement, VarStatement, WhileStatement,
+        BlockStatement, BreakStatement, ClassStatement, ContinueStatement, ExpressionStatement,
+        ForInStatement, ForStatement, FunctionStatement, IfStatement, ReturnStatement, Statement,
+        ThrowStatement, TryStatement, VarStatement, WhileStatement,
     },
     token::Token,
-    value::FunctionKind,
+    value::{FunctionKind, Value},
 };
 
 pub struct Resolver<'a> {
-    interpreter: &'a Interpreter,
+    interpreter: &'a mut Interpreter,
     scopes: Vec<HashMap<String, bool>>,
+    // Globals declared anywhere at the top level of the code being resolved, so uses can come first.
+    globals: HashSet<String>,
+    // Names declared with const, one set per scope. Global ones are kept by the interpreter.
+    constants: Vec<HashSet<String>>,
+    // Locals declared with let or const that haven't been read yet, one map per scope.
+    unread: Vec<HashMap<String, Token>>,
     current_function: Option<FunctionKind>,
+    loops: Vec<Option<String>>,
 }
 
 impl<'a> Resolver<'a> {
-    pub fn new(interpreter: &'a Interpreter) -> Self {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
         Self {
             interpreter,
             scopes: vec![],
+            globals: HashSet::new(),
+            constants: vec![],
+            unread: vec![],
             current_function: None,
+            loops: vec![],
         }
     }
 
     fn begin_scope(&mut self) {
-        self.scopes.push(HashMap::new())
+        self.scopes.push(HashMap::new());
+        self.constants.push(HashSet::new());
+        self.unread.push(HashMap::new());
     }
 
     fn end_scope(&mut self) {
         self.scopes.pop();
+        self.constants.pop();
+        if let Some(unread) = self.unread.pop() {
+            if self.interpreter.warns_unused() {
+                let mut unread = unread.into_values().collect::<Vec<_>>();
+                unread.sort_by_key(|x| x.line);
+                for name in unread {
+                    get_err_handler().warning(
+                        name.clone(),
+                        &format!("Local variable '{}' is never read.", name.lexeme),
+                    );
+                }
+            }
+        }
+    }
+
+    fn mark_read(&mut self, name: &Token) {
+        for (scope, unread) in self.scopes.iter().zip(&mut self.unread).rev() {
+            if scope.contains_key(&name.lexeme) {
+                unread.remove(&name.lexeme);
+                return;
+            }
+        }
+    }
+
+    fn current_constants(&mut self) -> &mut HashSet<String> {
+        self.constants
+            .last_mut()
+            .unwrap_or(self.interpreter.global_constants_mut())
+    }
+
+    // Finds the scope the name resolves to, like resolve_local does.
+    fn is_constant(&self, name: &Token) -> bool {
+        for (scope, constants) in self.scopes.iter().zip(&self.constants).rev() {
+            if scope.contains_key(&name.lexeme) {
+                return constants.contains(&name.lexeme);
+            }
+        }
+        self.interpreter.is_global_constant(&name.lexeme)
     }
 
     fn declare(&mut self, name: &Token) {
         if self.scopes.is_empty() {
+            // Globals can be declared again, except over a constant.
+            if self.interpreter.is_global_constant(&name.lexeme) {
+                get_err_handler().error(
+                    name.clone(),
+                    &format!("Cannot redeclare constant '{}'.", name.lexeme),
+                );
+            }
             return;
         }
 
-        let scope = self.scopes.first_mut().unwrap();
+        let scope = self.scopes.last_mut().unwrap();
         if scope.contains_key(&name.lexeme) {
-            get_err_handler().error(name.clone(), "Variable already exists in this scope.");
+            get_err_handler().error(
+                name.clone(),
+                &format!("Variable '{}' already declared in this scope.", name.lexeme),
+            );
         }
         scope.insert(name.lexeme.clone(), false);
     }
@@ -55,7 +117,7 @@ impl<'a> Resolver<'a> {
             return;
         }
 
-        let scope = self.scopes.first_mut().unwrap();
+        let scope = self.scopes.last_mut().unwrap();
         *scope.get_mut(&name.lexeme).unwrap() = true;
     }
 
@@ -66,25 +128,59 @@ impl<'a> Resolver<'a> {
     }
 
     fn resolve_var_statement(&mut self, statement: &mut VarStatement) {
-        self.declare(&statement.name);
-        if let Some(x) = &mut statement.initializer {
+        // Unpacked before any of the names exist.
+        if let Some(x) = &mut statement.unpack {
             self.resolve_expression(x);
         }
-        self.define(&statement.name);
+        // Each initializer can use the variables declared before it.
+        for declaration in &mut statement.declarations {
+            let name = &declaration.name;
+            self.declare(name);
+            if let Some(x) = &mut declaration.initializer {
+                self.resolve_expression(x);
+            }
+            self.define(name);
+            if statement.constant {
+                self.current_constants().insert(name.lexeme.clone());
+            }
+            // Names starting with '_' are unused on purpose.
+            if let Some(unread) = self.unread.last_mut() {
+                if !name.lexeme.starts_with('_') {
+                    unread.insert(name.lexeme.clone(), name.clone());
+                }
+            }
+        }
     }
 
     fn resolve_function(&mut self, function: &mut FunctionStatement, kind: FunctionKind) {
         let enclosing_function = self.current_function;
         self.current_function = Some(kind);
+        // Loops outside the function can't be broken out of from inside it.
+        let enclosing_loops = std::mem::take(&mut self.loops);
 
         self.begin_scope();
-        for param in &function.params {
+        for param in function.params.iter().chain(&function.rest) {
             self.declare(param);
             self.define(param);
         }
         self.resolve(function.body.iter_mut());
         self.end_scope();
 
+        // Only functions that return a value somewhere are expected to return one everywhere.
+        if self.interpreter.is_strict()
+            && has_value_return(&function.body)
+            && !always_returns(&function.body)
+        {
+            get_err_handler().warning(
+                function.name.clone(),
+                &format!(
+                    "Function '{}' doesn't return a value on every path.",
+                    function.name.lexeme
+                ),
+            );
+        }
+
+        self.loops = enclosing_loops;
         self.current_function = enclosing_function;
     }
 
@@ -114,14 +210,92 @@ impl<'a> Resolver<'a> {
             );
         }
 
-        if let Some(x) = &mut statement.expr {
-            self.resolve_expression(x);
+        for value in &mut statement.values {
+            self.resolve_expression(value);
         }
     }
 
     fn resolve_while_statement(&mut self, statement: &mut WhileStatement) {
         self.resolve_expression(&mut statement.condition);
+        self.loops
+            .push(statement.label.as_ref().map(|x| x.lexeme.clone()));
+        self.resolve_block_statement(&mut statement.body);
+        self.loops.pop();
+        // The else block is outside the loop, so break and continue there target an outer loop.
+        if let Some(x) = &mut statement.else_branch {
+            self.resolve_block_statement(x);
+        }
+    }
+
+    fn resolve_for_statement(&mut self, statement: &mut ForStatement) {
+        // The initializer gets its own scope so the loop variable outlives each iteration's body.
+        self.begin_scope();
+        if let Some(x) = &mut statement.initializer {
+            self.resolve_statement(x);
+        }
+        if let Some(x) = &mut statement.condition {
+            self.resolve_expression(x);
+        }
+        if let Some(x) = &mut statement.increment {
+            self.resolve_expression(x);
+        }
+        self.loops
+            .push(statement.label.as_ref().map(|x| x.lexeme.clone()));
+        self.resolve_block_statement(&mut statement.body);
+        self.loops.pop();
+        self.end_scope();
+    }
+
+    fn resolve_for_in_statement(&mut self, statement: &mut ForInStatement) {
+        self.resolve_expression(&mut statement.iterable);
+        self.begin_scope();
+        self.declare(&statement.name);
+        self.define(&statement.name);
+        self.loops
+            .push(statement.label.as_ref().map(|x| x.lexeme.clone()));
+        self.resolve_block_statement(&mut statement.body);
+        self.loops.pop();
+        self.end_scope();
+    }
+
+    fn resolve_loop_jump(&mut self, keyword: &Token, label: &Option<Token>) {
+        if self.loops.is_empty() {
+            get_err_handler().error(
+                keyword.clone(),
+                &format!("Can't {} outside of a loop.", keyword.lexeme),
+            );
+            return;
+        }
+
+        if let Some(label) = label {
+            if !self.loops.iter().flatten().any(|x| x == &label.lexeme) {
+                get_err_handler().error(
+                    label.clone(),
+                    &format!("Undefined loop label '{}'.", label.lexeme),
+                );
+            }
+        }
+    }
+
+    fn resolve_break_statement(&mut self, statement: &mut BreakStatement) {
+        self.resolve_loop_jump(&statement.keyword, &statement.label);
+    }
+
+    fn resolve_continue_statement(&mut self, statement: &mut ContinueStatement) {
+        self.resolve_loop_jump(&statement.keyword, &statement.label);
+    }
+
+    fn resolve_try_statement(&mut self, statement: &mut TryStatement) {
         self.resolve_block_statement(&mut statement.body);
+        self.begin_scope();
+        self.declare(&statement.name);
+        self.define(&statement.name);
+        self.resolve(statement.handler.statements.iter_mut());
+        self.end_scope();
+    }
+
+    fn resolve_throw_statement(&mut self, statement: &mut ThrowStatement) {
+        self.resolve_expression(&mut statement.expr);
     }
 
     fn resolve_class_statement(&mut self, statement: &mut ClassStatement) {
@@ -132,11 +306,31 @@ impl<'a> Resolver<'a> {
     fn resolve_local(&mut self, expression: &mut Expression, name: &Token) {
         for (i, scope) in self.scopes.iter().rev().enumerate() {
             if scope.contains_key(&name.lexeme) {
-                self.interpreter
-                    .resolve(expression, (self.scopes.len() - 1 - i) as u32);
-                break;
+                self.interpreter.resolve(expression, i as u32);
+                return;
             }
         }
+        self.check_global(name);
+    }
+
+    // Globals can still be defined at runtime, so this is only a warning unless in strict mode.
+    fn check_global(&self, name: &Token) {
+        if self.globals.contains(&name.lexeme)
+            || self
+                .interpreter
+                .get_global_env()
+                .borrow()
+                .contains(&name.lexeme)
+        {
+            return;
+        }
+
+        let msg = format!("Undefined variable '{}'.", name.lexeme);
+        if self.interpreter.is_strict() {
+            get_err_handler().error(name.clone(), &msg);
+        } else {
+            get_err_handler().warning(name.clone(), &msg);
+        }
     }
 
     fn resolve_var_expression(&mut self, expression: &mut Expression) {
@@ -146,7 +340,7 @@ impl<'a> Resolver<'a> {
             return;
         };
 
-        let top_scope = self.scopes.first();
+        let top_scope = self.scopes.last();
         if let Some(x) = top_scope {
             let is_var_in_top = x.get(&var_expr.name.lexeme);
             if !self.scopes.is_empty()
@@ -160,6 +354,7 @@ impl<'a> Resolver<'a> {
         }
 
         let name = var_expr.name.clone();
+        self.mark_read(&name);
         self.resolve_local(expression, &name);
     }
 
@@ -171,6 +366,12 @@ impl<'a> Resolver<'a> {
         };
 
         self.resolve_expression(&mut assign_expr.value);
+        if self.is_constant(&assign_expr.name) {
+            get_err_handler().error(
+                assign_expr.name.clone(),
+                &format!("Cannot assign to constant '{}'.", assign_expr.name.lexeme),
+            );
+        }
         let name = assign_expr.name.clone();
         self.resolve_local(expression, &name);
     }
@@ -233,13 +434,121 @@ impl<'a> Resolver<'a> {
             Statement::If(x) => self.resolve_if_statement(x),
             Statement::Return(x) => self.resolve_return_statement(x),
             Statement::While(x) => self.resolve_while_statement(x),
+            Statement::For(x) => self.resolve_for_statement(x),
+            Statement::ForIn(x) => self.resolve_for_in_statement(x),
+            Statement::Break(x) => self.resolve_break_statement(x),
+            Statement::Continue(x) => self.resolve_continue_statement(x),
+            Statement::Try(x) => self.resolve_try_statement(x),
+            Statement::Throw(x) => self.resolve_throw_statement(x),
             Statement::Class(x) => self.resolve_class_statement(x),
         }
     }
 
     pub fn resolve<'b>(&mut self, statements: impl Iterator<Item = &'b mut Statement>) {
+        let statements = statements.collect::<Vec<_>>();
+        if self.scopes.is_empty() {
+            for statement in &statements {
+                let name = match statement {
+                    Statement::Var(x) => {
+                        for declaration in &x.declarations {
+                            self.globals.insert(declaration.name.lexeme.clone());
+                        }
+                        continue;
+                    }
+                    Statement::Function(x) => &x.name,
+                    Statement::Class(x) => &x.name,
+                    _ => continue,
+                };
+                self.globals.insert(name.lexeme.clone());
+            }
+        }
+
         for statement in statements {
             self.resolve_statement(statement);
         }
     }
 }
+
+// These walks stop at nested function declarations, their returns belong to the nested function.
+
+fn has_value_return(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::Return(x) => !x.values.is_empty(),
+        Statement::Block(x) => has_value_return(&x.statements),
+        Statement::If(x) => {
+            has_value_return(&x.then_branch.statements)
+                || x.else_branch
+                    .as_ref()
+                    .is_some_and(|x| has_value_return(&x.statements))
+        }
+        Statement::While(x) => {
+            has_value_return(&x.body.statements)
+                || x.else_branch
+                    .as_ref()
+                    .is_some_and(|x| has_value_return(&x.statements))
+        }
+        Statement::For(x) => has_value_return(&x.body.statements),
+        Statement::ForIn(x) => has_value_return(&x.body.statements),
+        Statement::Try(x) => {
+            has_value_return(&x.body.statements) || has_value_return(&x.handler.statements)
+        }
+        _ => false,
+    })
+}
+
+/// Whether running the statements always ends in a return or throw.
+fn always_returns(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::Return(_) | Statement::Throw(_) => true,
+        Statement::Block(x) => always_returns(&x.statements),
+        Statement::If(x) => {
+            always_returns(&x.then_branch.statements)
+                && x.else_branch
+                    .as_ref()
+                    .is_some_and(|x| always_returns(&x.statements))
+        }
+        // 'while true' only ends through a break, or through its else block which can't run.
+        Statement::While(x) => {
+            is_literal_true(&x.condition) && !breaks_out(&x.body.statements, false)
+        }
+        Statement::Try(x) => {
+            always_returns(&x.body.statements) && always_returns(&x.handler.statements)
+        }
+        _ => false,
+    })
+}
+
+fn is_literal_true(expr: &Expression) -> bool {
+    match expr {
+        Expression::Literal(x) => matches!(x.value, Value::Boolean(true)),
+        Expression::Grouping(x) => is_literal_true(&x.expr),
+        _ => false,
+    }
+}
+
+// Whether a break in a loop body can leave the loop. Inside nested loops only labeled breaks can,
+// and any label is assumed to reach this far.
+fn breaks_out(statements: &[Statement], nested: bool) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::Break(x) => x.label.is_some() || !nested,
+        Statement::Block(x) => breaks_out(&x.statements, nested),
+        Statement::If(x) => {
+            breaks_out(&x.then_branch.statements, nested)
+                || x.else_branch
+                    .as_ref()
+                    .is_some_and(|x| breaks_out(&x.statements, nested))
+        }
+        Statement::Try(x) => {
+            breaks_out(&x.body.statements, nested) || breaks_out(&x.handler.statements, nested)
+        }
+        Statement::While(x) => {
+            breaks_out(&x.body.statements, true)
+                || x.else_branch
+                    .as_ref()
+                    .is_some_and(|x| breaks_out(&x.statements, nested))
+        }
+        Statement::For(x) => breaks_out(&x.body.statements, true),
+        Statement::ForIn(x) => breaks_out(&x.body.statements, true),
+        _ => false,
+    })
+}
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use crate::{expression::Expression, token::Token};
 
 #[derive(Debug, Clone)]
@@ -6,16 +8,29 @@ pub struct ExpressionStatement {
 }
 
 #[derive(Debug, Clone)]
-pub struct VarStatement {
+pub struct VarDeclaration {
     pub name: Token,
     pub initializer: Option<Expression>,
 }
 
+/// One or more variables declared by a single let or const, defined left to right.
+#[derive(Debug, Clone)]
+pub struct VarStatement {
+    pub declarations: Vec<VarDeclaration>,
+    pub constant: bool,
+    /// A list whose items are assigned to the declarations in order, as in `let x, y = f()`.
+    /// The declarations have no initializers of their own then.
+    pub unpack: Option<Expression>,
+}
+
 #[derive(Debug, Clone)]
 pub struct FunctionStatement {
     pub name: Token,
     pub params: Vec<Token>,
+    pub rest: Option<Token>,
     pub body: Vec<Statement>,
+    /// The declaration's text, for functions declared at the top level, so sessions can save them.
+    pub source: Option<Rc<str>>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +40,7 @@ pub struct BlockStatement {
 
 #[derive(Debug, Clone)]
 pub struct IfStatement {
+    pub keyword: Token,
     pub condition: Expression,
     pub then_branch: BlockStatement,
     pub else_branch: Option<BlockStatement>,
@@ -32,14 +48,63 @@ pub struct IfStatement {
 
 #[derive(Debug, Clone)]
 pub struct WhileStatement {
+    pub keyword: Token,
+    pub label: Option<Token>,
     pub condition: Expression,
     pub body: BlockStatement,
+    // Runs when the condition turns false, but not after a break.
+    pub else_branch: Option<BlockStatement>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForStatement {
+    pub keyword: Token,
+    pub label: Option<Token>,
+    pub initializer: Option<Box<Statement>>,
+    pub condition: Option<Expression>,
+    pub increment: Option<Expression>,
+    pub body: BlockStatement,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForInStatement {
+    pub label: Option<Token>,
+    pub name: Token,
+    pub iterable: Expression,
+    pub body: BlockStatement,
 }
 
 #[derive(Debug, Clone)]
 pub struct ReturnStatement {
     pub keyword: Token,
-    pub expr: Option<Expression>,
+    /// Empty returns none, several values are returned as a list.
+    pub values: Vec<Expression>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakStatement {
+    pub keyword: Token,
+    pub label: Option<Token>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContinueStatement {
+    pub keyword: Token,
+    pub label: Option<Token>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TryStatement {
+    pub keyword: Token,
+    pub body: BlockStatement,
+    pub name: Token,
+    pub handler: BlockStatement,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThrowStatement {
+    pub keyword: Token,
+    pub expr: Expression,
 }
 
 #[derive(Debug, Clone)]
@@ -56,6 +121,54 @@ pub enum Statement {
     Block(BlockStatement),
     If(IfStatement),
     While(WhileStatement),
+    For(ForStatement),
+    ForIn(ForInStatement),
     Return(ReturnStatement),
+    Break(BreakStatement),
+    Continue(ContinueStatement),
+    Try(TryStatement),
+    Throw(ThrowStatement),
     Class(ClassStatement),
 }
+
+impl Statement {
+    /// The line the statement starts on, if it keeps a token from it. Blocks don't.
+    pub fn get_line(&self) -> Option<usize> {
+        match self {
+            Statement::Expression(x) => x.expr.get_line(),
+            Statement::Var(x) => Some(x.declarations[0].name.line),
+            Statement::Function(x) => Some(x.name.line),
+            Statement::Block(_) => None,
+            Statement::If(x) => Some(x.keyword.line),
+            Statement::While(x) => Some(x.keyword.line),
+            Statement::For(x) => Some(x.keyword.line),
+            Statement::ForIn(x) => Some(x.name.line),
+            Statement::Return(x) => Some(x.keyword.line),
+            Statement::Break(x) => Some(x.keyword.line),
+            Statement::Continue(x) => Some(x.keyword.line),
+            Statement::Try(x) => Some(x.keyword.line),
+            Statement::Throw(x) => Some(x.keyword.line),
+            Statement::Class(x) => Some(x.name.line),
+        }
+    }
+
+    /// A short name for the kind of statement, for traces.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Statement::Expression(_) => "expression",
+            Statement::Var(_) => "var",
+            Statement::Function(_) => "fn",
+            Statement::Block(_) => "block",
+            Statement::If(_) => "if",
+            Statement::While(_) => "while",
+            Statement::For(_) => "for",
+            Statement::ForIn(_) => "for in",
+            Statement::Return(_) => "return",
+            Statement::Break(_) => "break",
+            Statement::Continue(_) => "continue",
+            Statement::Try(_) => "try",
+            Statement::Throw(_) => "throw",
+            Statement::Class(_) => "class",
+        }
+    }
+}
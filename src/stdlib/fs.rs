@@ -0,0 +1,65 @@
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader},
+};
+
+use crate::environment::EnvPtr;
+use crate::interpreter::Interpreter;
+use crate::value::{NativeFunction, NativeFunctionResult, Value};
+
+pub fn register(interpreter: &mut Interpreter) {
+    interpreter.register_native(NativeFunction::new("read_file", 1, read_file));
+    interpreter.register_native(NativeFunction::new("write_file", 2, write_file));
+    interpreter.register_native(NativeFunction::new("open_file", 1, open_file));
+    interpreter.register_native(NativeFunction::new("read_line", 1, read_line));
+}
+
+fn expect_path<'a>(value: &'a Value, func_name: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(x) => Ok(x),
+        x => Err(format!(
+            "{func_name} expected a path string, but got '{x}'."
+        )),
+    }
+}
+
+pub fn read_file(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let path = expect_path(&values[0], "read_file")?;
+    match fs::read_to_string(path) {
+        Ok(x) => Ok(Value::String(x)),
+        Err(err) => Err(format!("Could not read '{path}': {err}.").into()),
+    }
+}
+
+/// Writes the printed form of the value, returning whether it succeeded.
+pub fn write_file(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let path = expect_path(&values[0], "write_file")?;
+    Ok(Value::Boolean(
+        fs::write(path, values[1].to_string()).is_ok(),
+    ))
+}
+
+/// Opens a file for reading line by line, it is closed once the handle is no longer used.
+pub fn open_file(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let path = expect_path(&values[0], "open_file")?;
+    match File::open(path) {
+        Ok(x) => Ok(Value::foreign("file", BufReader::new(x))),
+        Err(err) => Err(format!("Could not open '{path}': {err}.").into()),
+    }
+}
+
+/// The next line of an open file without its line ending, or none at the end of the file.
+pub fn read_line(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let line = values[0].with_foreign(|file: &mut BufReader<File>| {
+        let mut line = String::new();
+        file.read_line(&mut line).map(|count| (count, line))
+    });
+    match line {
+        Some(Ok((0, _))) => Ok(Value::None),
+        Some(Ok((_, line))) => Ok(Value::String(
+            line.trim_end_matches(['\n', '\r']).to_owned(),
+        )),
+        Some(Err(err)) => Err(format!("Could not read line: {err}.").into()),
+        None => Err(format!("read_line expected a file, but got '{}'.", values[0]).into()),
+    }
+}
@@ -1,22 +1,46 @@
+use std::io::{stdout, Write};
+
 use crate::environment::EnvPtr;
 use crate::interpreter::Interpreter;
 use crate::value::{NativeFunction, NativeFunctionResult, Value};
 
 pub fn register(interpreter: &mut Interpreter) {
-    interpreter.register_native(NativeFunction::new("print", 1, print));
-    interpreter.register_native(NativeFunction::new("print_line", 1, print_line));
+    interpreter.register_native(NativeFunction::new_variadic("print", 0, print));
+    interpreter.register_native(NativeFunction::new_variadic("println", 0, println));
+    // Kept so older scripts still run.
+    interpreter.register_native(NativeFunction::new_variadic("print_line", 0, println));
+    interpreter.register_native(NativeFunction::new("flush", 0, flush));
     interpreter.register_native(NativeFunction::new("test_err", 0, test_err));
 }
 
+fn join(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Both print natives flush, so output shows up before a prompt that follows it. That costs a
+// write call per print, which only matters in tight loops printing a lot.
+
+/// Prints the values separated by spaces, without a trailing newline.
 pub fn print(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
-    let val = values.first().unwrap();
-    print!("{}", val);
+    let mut stdout = stdout().lock();
+    write!(stdout, "{}", join(&values))?;
+    stdout.flush()?;
+    Ok(Value::None)
+}
+
+pub fn println(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let mut stdout = stdout().lock();
+    writeln!(stdout, "{}", join(&values))?;
+    stdout.flush()?;
     Ok(Value::None)
 }
 
-pub fn print_line(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
-    let val = values.first().unwrap();
-    println!("{}", val);
+pub fn flush(_env: EnvPtr, _values: Vec<Value>) -> NativeFunctionResult {
+    stdout().flush()?;
     Ok(Value::None)
 }
 
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use crate::environment::EnvPtr;
+use crate::interpreter::Interpreter;
+use crate::value::{NativeFunction, NativeFunctionResult, SharedPtr, Value};
+
+use super::expect_index;
+
+/// The most items `range` will build, so a typo like `range(0, 1e12)` errors instead of running
+/// out of memory. This is fixed rather than an interpreter setting like the fuel, since natives
+/// only get the environment and can't read settings from the interpreter.
+pub const MAX_RANGE_LEN: usize = 10_000_000;
+
+pub fn register(interpreter: &mut Interpreter) {
+    interpreter.register_native(NativeFunction::new_ranged("range", 2, 3, range));
+    interpreter.register_native(NativeFunction::new("copy", 1, copy));
+    interpreter.register_native(NativeFunction::new("deep_copy", 1, deep_copy));
+    interpreter.register_native(NativeFunction::new("push", 2, push));
+    interpreter.register_native(NativeFunction::new("pop", 1, pop));
+    interpreter.register_native(NativeFunction::new("insert", 3, insert));
+    interpreter.register_native(NativeFunction::new("remove", 2, remove));
+    interpreter.register_native(NativeFunction::new("sort", 1, sort));
+}
+
+fn expect_list<'a>(value: &'a Value, func_name: &str) -> Result<&'a SharedPtr<Vec<Value>>, String> {
+    match value {
+        Value::List(x) => Ok(x),
+        x => Err(format!("{func_name} expected a list, but got '{x}'.")),
+    }
+}
+
+fn expect_number(value: &Value, func_name: &str) -> Result<f64, String> {
+    match value {
+        Value::Number(x) if x.is_finite() => Ok(*x),
+        x => Err(format!(
+            "{func_name} expected a finite number, but got '{x}'."
+        )),
+    }
+}
+
+/// range(start, end, step = 1): the numbers from start up to, but not including, end.
+pub fn range(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let start = expect_number(&values[0], "range")?;
+    let end = expect_number(&values[1], "range")?;
+    let step = match values.get(2) {
+        Some(x) => expect_number(x, "range")?,
+        None => 1.0,
+    };
+    if step == 0.0 {
+        return Err("range step can't be 0.".into());
+    }
+
+    let len = ((end - start) / step).ceil().max(0.0);
+    if len > MAX_RANGE_LEN as f64 {
+        return Err(format!("range would have {len} items, the limit is {MAX_RANGE_LEN}.").into());
+    }
+    let items = (0..len as usize)
+        .map(|i| Value::Number(start + i as f64 * step))
+        .collect();
+    Ok(Value::List(SharedPtr::new(items)))
+}
+
+/// A new list with the same items. Values other than lists are returned as they are.
+pub fn copy(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    match &values[0] {
+        Value::List(x) => Ok(Value::List(SharedPtr::new(x.borrow().clone()))),
+        x => Ok(x.clone()),
+    }
+}
+
+/// Copies lists and every list nested in them.
+pub fn deep_copy(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    Ok(copy_recursive(&values[0], &mut HashMap::new()))
+}
+
+// Maps each list already copied to its copy, so shared and self-containing lists keep their shape.
+fn copy_recursive(value: &Value, copies: &mut HashMap<usize, SharedPtr<Vec<Value>>>) -> Value {
+    let list = match value {
+        Value::List(x) => x,
+        x => return x.clone(),
+    };
+    if let Some(x) = copies.get(&(list.as_ptr() as usize)) {
+        return Value::List(x.clone());
+    }
+
+    let copy = SharedPtr::new(Vec::with_capacity(list.borrow().len()));
+    copies.insert(list.as_ptr() as usize, copy.clone());
+    for item in list.borrow().iter() {
+        let item = copy_recursive(item, copies);
+        copy.borrow_mut().push(item);
+    }
+    Value::List(copy)
+}
+
+// The mutating natives change the list in place, so every variable holding it sees the change.
+
+/// Adds the value to the end of the list.
+pub fn push(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let list = expect_list(&values[0], "push")?;
+    list.borrow_mut().push(values[1].clone());
+    Ok(Value::None)
+}
+
+/// Removes and returns the last item.
+pub fn pop(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let list = expect_list(&values[0], "pop")?;
+    let item = list.borrow_mut().pop();
+    item.ok_or_else(|| "pop called on an empty list.".into())
+}
+
+/// Inserts the value before the item at the index, an index equal to the length appends.
+pub fn insert(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let list = expect_list(&values[0], "insert")?;
+    let index = expect_index(&values[1], "insert")?;
+    let mut list = list.borrow_mut();
+    if index > list.len() {
+        return Err(format!(
+            "insert index {index} is out of range for a list of length {}.",
+            list.len()
+        )
+        .into());
+    }
+    list.insert(index, values[2].clone());
+    Ok(Value::None)
+}
+
+/// Removes and returns the item at the index.
+pub fn remove(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let list = expect_list(&values[0], "remove")?;
+    let index = expect_index(&values[1], "remove")?;
+    let mut list = list.borrow_mut();
+    if index >= list.len() {
+        return Err(format!(
+            "remove index {index} is out of range for a list of length {}.",
+            list.len()
+        )
+        .into());
+    }
+    Ok(list.remove(index))
+}
+
+/// Sorts a list of only numbers or only strings in place, NaN goes last.
+pub fn sort(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let list = expect_list(&values[0], "sort")?;
+    let mut list = list.borrow_mut();
+    let numbers = list.iter().map(|x| match x {
+        Value::Number(x) => Some(*x),
+        _ => None,
+    });
+    if let Some(mut numbers) = numbers.collect::<Option<Vec<_>>>() {
+        numbers.sort_by(f64::total_cmp);
+        *list = numbers.into_iter().map(Value::Number).collect();
+        return Ok(Value::None);
+    }
+    let strings = list.iter().map(|x| match x {
+        Value::String(x) => Some(x.clone()),
+        _ => None,
+    });
+    if let Some(mut strings) = strings.collect::<Option<Vec<_>>>() {
+        strings.sort();
+        *list = strings.into_iter().map(Value::String).collect();
+        return Ok(Value::None);
+    }
+    Err("sort expected a list of only numbers or only strings.".into())
+}
@@ -0,0 +1,16 @@
+use crate::environment::EnvPtr;
+use crate::interpreter::Interpreter;
+use crate::value::{NativeFunction, NativeFunctionResult, Value};
+
+pub fn register(interpreter: &mut Interpreter) {
+    interpreter.register_native(NativeFunction::new("is_nan", 1, is_nan));
+}
+
+/// NaN follows IEEE 754: `is`, `<`, `<=`, `>` and `>=` are all false when either side is NaN,
+/// even `nan is nan`, so this is the only way to test for it.
+pub fn is_nan(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    match &values[0] {
+        Value::Number(x) => Ok(Value::Boolean(x.is_nan())),
+        x => Err(format!("is_nan expected a number, but got '{x}'.").into()),
+    }
+}
@@ -1,7 +1,35 @@
-use crate::interpreter::Interpreter;
+use crate::{interpreter::Interpreter, value::Value};
 
+#[cfg(feature = "fs")]
+mod fs;
 mod io_utils;
+mod lists;
+mod math;
+#[cfg(feature = "os")]
+mod os;
+mod strings;
+mod testing;
+mod time;
 
 pub fn register(interpreter: &mut Interpreter) {
-    io_utils::register(interpreter)
+    #[cfg(feature = "fs")]
+    fs::register(interpreter);
+    io_utils::register(interpreter);
+    lists::register(interpreter);
+    math::register(interpreter);
+    #[cfg(feature = "os")]
+    os::register(interpreter);
+    strings::register(interpreter);
+    testing::register(interpreter);
+    time::register(interpreter);
+}
+
+// Shared by natives that take an index into a string or list.
+fn expect_index(value: &Value, func_name: &str) -> Result<usize, String> {
+    match value {
+        Value::Number(x) if *x >= 0.0 && x.fract() == 0.0 => Ok(*x as usize),
+        x => Err(format!(
+            "{func_name} expected a non-negative whole number, but got '{x}'."
+        )),
+    }
 }
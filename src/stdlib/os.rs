@@ -0,0 +1,22 @@
+use std::env;
+
+use crate::environment::EnvPtr;
+use crate::interpreter::Interpreter;
+use crate::value::{NativeFunction, NativeFunctionResult, Value};
+
+pub fn register(interpreter: &mut Interpreter) {
+    interpreter.register_native(NativeFunction::new("env", 1, env));
+}
+
+/// The value of an environment variable, or none when it isn't set.
+pub fn env(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let name = match &values[0] {
+        Value::String(x) => x,
+        x => return Err(format!("env expected a variable name, but got '{x}'.").into()),
+    };
+    match env::var(name) {
+        Ok(x) => Ok(Value::String(x)),
+        Err(env::VarError::NotPresent) => Ok(Value::None),
+        Err(err) => Err(format!("Could not read '{name}': {err}.").into()),
+    }
+}
@@ -0,0 +1,148 @@
+use crate::environment::EnvPtr;
+use crate::interpreter::Interpreter;
+use crate::value::{NativeFunction, NativeFunctionResult, SharedPtr, Value};
+
+use super::expect_index;
+
+pub fn register(interpreter: &mut Interpreter) {
+    interpreter.register_native(NativeFunction::new("upper", 1, upper));
+    interpreter.register_native(NativeFunction::new("lower", 1, lower));
+    interpreter.register_native(NativeFunction::new("trim", 1, trim));
+    interpreter.register_native(NativeFunction::new("split", 2, split));
+    interpreter.register_native(NativeFunction::new("substr", 3, substr));
+    interpreter.register_native(NativeFunction::new("char_at", 2, char_at));
+    interpreter.register_native(NativeFunction::new("contains", 2, contains));
+    interpreter.register_native(NativeFunction::new("starts_with", 2, starts_with));
+    interpreter.register_native(NativeFunction::new("ends_with", 2, ends_with));
+    interpreter.register_native(NativeFunction::new_variadic("format", 1, format));
+}
+
+fn expect_string<'a>(value: &'a Value, func_name: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(x) => Ok(x),
+        x => Err(format!("{func_name} expected a string, but got '{x}'.")),
+    }
+}
+
+pub fn upper(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let string = expect_string(&values[0], "upper")?;
+    Ok(Value::String(string.to_uppercase()))
+}
+
+pub fn lower(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let string = expect_string(&values[0], "lower")?;
+    Ok(Value::String(string.to_lowercase()))
+}
+
+pub fn trim(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let string = expect_string(&values[0], "trim")?;
+    Ok(Value::String(string.trim().to_owned()))
+}
+
+pub fn split(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let string = expect_string(&values[0], "split")?;
+    let separator = expect_string(&values[1], "split")?;
+    if separator.is_empty() {
+        return Err("split separator can't be empty.".into());
+    }
+    let parts = string
+        .split(separator)
+        .map(|x| Value::String(x.to_owned()))
+        .collect();
+    Ok(Value::List(SharedPtr::new(parts)))
+}
+
+pub fn contains(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let string = expect_string(&values[0], "contains")?;
+    let needle = expect_string(&values[1], "contains")?;
+    Ok(Value::Boolean(string.contains(needle)))
+}
+
+pub fn starts_with(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let string = expect_string(&values[0], "starts_with")?;
+    let prefix = expect_string(&values[1], "starts_with")?;
+    Ok(Value::Boolean(string.starts_with(prefix)))
+}
+
+pub fn ends_with(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let string = expect_string(&values[0], "ends_with")?;
+    let suffix = expect_string(&values[1], "ends_with")?;
+    Ok(Value::Boolean(string.ends_with(suffix)))
+}
+
+// Indices count chars rather than bytes, so a multi-byte character is never split.
+
+pub fn substr(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let string = expect_string(&values[0], "substr")?;
+    let start = expect_index(&values[1], "substr")?;
+    let len = expect_index(&values[2], "substr")?;
+    let char_count = string.chars().count();
+    if start.checked_add(len).is_none_or(|end| end > char_count) {
+        return Err(format!(
+            "substr of length {} from {} is out of bounds for string of length {}.",
+            len, start, char_count
+        )
+        .into());
+    }
+    Ok(Value::String(
+        string.chars().skip(start).take(len).collect(),
+    ))
+}
+
+pub fn char_at(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let string = expect_string(&values[0], "char_at")?;
+    let index = expect_index(&values[1], "char_at")?;
+    match string.chars().nth(index) {
+        Some(x) => Ok(Value::String(x.to_string())),
+        None => Err(format!(
+            "char_at index {} is out of bounds for string of length {}.",
+            index,
+            string.chars().count()
+        )
+        .into()),
+    }
+}
+
+// Replaces each {} in the template with the next argument. {{ and }} produce literal braces.
+pub fn format(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    let template = expect_string(&values[0], "format")?;
+    let mut args = values[1..].iter();
+    let mut buf = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                buf.push(ch);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                match args.next() {
+                    Some(x) => buf.push_str(&x.to_string()),
+                    None => {
+                        return Err(format!(
+                            "format has more placeholders than the {} arguments given.",
+                            values.len() - 1
+                        )
+                        .into())
+                    }
+                }
+            }
+            ('{', _) | ('}', _) => {
+                return Err(format!(
+                    "Unmatched '{ch}' in format string. Use '{ch}{ch}' for a literal brace."
+                )
+                .into())
+            }
+            _ => buf.push(ch),
+        }
+    }
+    if args.next().is_some() {
+        return Err(format!(
+            "format was given {} arguments, but has fewer placeholders.",
+            values.len() - 1
+        )
+        .into());
+    }
+    Ok(Value::String(buf))
+}
@@ -0,0 +1,26 @@
+use crate::environment::EnvPtr;
+use crate::interpreter::Interpreter;
+use crate::value::{NativeFunction, NativeFunctionResult, Value};
+
+pub fn register(interpreter: &mut Interpreter) {
+    interpreter.register_native(NativeFunction::new("assert", 1, assert));
+    interpreter.register_native(NativeFunction::new("assert_eq", 2, assert_eq));
+}
+
+pub fn assert(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    if !Interpreter::is_truthy(&values[0]) {
+        return Err(format!("Assertion failed: got '{}'.", values[0]).into());
+    }
+    Ok(Value::None)
+}
+
+pub fn assert_eq(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    if !Interpreter::is_deep_equal(&values[0], &values[1]) {
+        return Err(format!(
+            "Assertion failed: '{}' is not equal to '{}'.",
+            values[0], values[1]
+        )
+        .into());
+    }
+    Ok(Value::None)
+}
@@ -0,0 +1,48 @@
+use std::{
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use once_cell::sync::Lazy;
+
+use crate::environment::EnvPtr;
+use crate::interpreter::Interpreter;
+use crate::value::{NativeFunction, NativeFunctionResult, Value};
+
+static START: Lazy<Instant> = Lazy::new(Instant::now);
+
+pub fn register(interpreter: &mut Interpreter) {
+    Lazy::force(&START);
+    interpreter.register_native(NativeFunction::new("clock", 0, clock));
+    interpreter.register_native(NativeFunction::new("now", 0, now));
+    interpreter.register_native(NativeFunction::new("sleep", 1, sleep));
+}
+
+/// Monotonic seconds since the interpreter started, for measuring durations.
+pub fn clock(_env: EnvPtr, _values: Vec<Value>) -> NativeFunctionResult {
+    Ok(Value::Number(START.elapsed().as_secs_f64()))
+}
+
+/// Wall-clock seconds since the Unix epoch.
+pub fn now(_env: EnvPtr, _values: Vec<Value>) -> NativeFunctionResult {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    Ok(Value::Number(since_epoch.as_secs_f64()))
+}
+
+/// Blocks the whole interpreter for the given number of seconds.
+pub fn sleep(_env: EnvPtr, values: Vec<Value>) -> NativeFunctionResult {
+    // Negative, NaN and too large numbers of seconds have no Duration.
+    let duration = match &values[0] {
+        Value::Number(x) => Duration::try_from_secs_f64(*x).ok(),
+        _ => None,
+    };
+    let Some(duration) = duration else {
+        return Err(format!(
+            "sleep expected a non-negative number of seconds, but got '{}'.",
+            values[0]
+        )
+        .into());
+    };
+    thread::sleep(duration);
+    Ok(Value::None)
+}
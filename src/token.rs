@@ -26,28 +26,44 @@ pub enum TokenType {
     MinusMinus,
     PlusEqual,
     MinusEqual,
+    Power,
+    EqualEqual,
+    BangEqual,
+
+    // Three characters
+    Ellipsis,
 
     // Literals
     String,
     Number,
     Identifier,
+    Label,
 
     // Keywords
     Let,
+    Const,
     Fn, // Function
     End,
     Ret, // Return
     Is,
+    IsNot, // Made by the parser from 'is' followed by 'not'.
     Not,
     And,
     Or,
+    Xor,
     Class,
     This,
     Super,
     While,
+    Break,
+    Continue,
     For,
+    In,
     If,
     Else,
+    Try,
+    Catch,
+    Throw,
     True,
     False,
     None,
@@ -5,7 +5,13 @@ use std::fmt::Debug;
 pub type CallableResult = Result<Value, RuntimeOrNativeError>;
 
 pub trait Callable<'a>: Debug {
-    fn call(&mut self, interpreter: &mut Interpreter, args: Vec<Value>) -> CallableResult;
+    fn call(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> CallableResult;
     fn get_arity(&self) -> usize;
     fn get_name(&self) -> String;
+    /// Variadic callables take get_arity() or more arguments.
+    fn is_variadic(&self) -> bool;
+    /// The most arguments a variadic callable takes, None when there is no limit.
+    fn get_max_arity(&self) -> Option<usize> {
+        None
+    }
 }
@@ -20,7 +20,7 @@ impl Display for Class {
 
 impl<'a> Callable<'a> for Class {
     fn call(
-        &mut self,
+        &self,
         _interpreter: &mut crate::interpreter::Interpreter,
         _args: Vec<super::Value>,
     ) -> CallableResult {
@@ -36,4 +36,8 @@ impl<'a> Callable<'a> for Class {
     fn get_name(&self) -> String {
         self.name.clone()
     }
+
+    fn is_variadic(&self) -> bool {
+        false
+    }
 }
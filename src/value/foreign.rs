@@ -0,0 +1,40 @@
+use std::{
+    any::Any,
+    fmt::{Debug, Display},
+};
+
+/// A Rust value that scripts can only pass around, for natives that hand out handles.
+///
+/// The wrapped value is dropped as soon as the last script reference to it goes away, so
+/// resources like open files are released by their own Drop impl.
+pub struct Foreign {
+    type_name: &'static str,
+    value: Box<dyn Any>,
+}
+
+impl Foreign {
+    pub fn new<T: Any>(type_name: &'static str, value: T) -> Self {
+        Self {
+            type_name,
+            value: Box::new(value),
+        }
+    }
+
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.value.downcast_mut()
+    }
+}
+
+impl Debug for Foreign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Foreign")
+            .field("type_name", &self.type_name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for Foreign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("<{}>", self.type_name))
+    }
+}
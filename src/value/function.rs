@@ -1,4 +1,4 @@
-use super::{callable::CallableResult, Callable};
+use super::{callable::CallableResult, Callable, SharedPtr};
 use crate::{
     environment::{EnvPtr, Environment},
     error::RuntimeError,
@@ -6,7 +6,7 @@ use crate::{
     statement::FunctionStatement,
     value::Value,
 };
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, rc::Rc};
 
 #[derive(Clone, Copy)]
 pub enum FunctionKind {
@@ -35,6 +35,8 @@ impl Display for FunctionKind {
 pub struct NativeFunction {
     name: String,
     arg_count: usize,
+    variadic: bool,
+    max_arg_count: Option<usize>,
     func: fn(env: EnvPtr, args: Vec<Value>) -> NativeFunctionResult,
 }
 
@@ -47,36 +49,73 @@ impl NativeFunction {
         Self {
             name: name.to_string(),
             arg_count,
+            variadic: false,
+            max_arg_count: None,
             func,
         }
     }
 
+    /// Creates a native that accepts min_arg_count or more arguments.
+    pub fn new_variadic(
+        name: impl ToString,
+        min_arg_count: usize,
+        func: fn(env: EnvPtr, args: Vec<Value>) -> NativeFunctionResult,
+    ) -> Self {
+        Self {
+            variadic: true,
+            ..Self::new(name, min_arg_count, func)
+        }
+    }
+
+    /// Creates a native that accepts between min_arg_count and max_arg_count arguments.
+    pub fn new_ranged(
+        name: impl ToString,
+        min_arg_count: usize,
+        max_arg_count: usize,
+        func: fn(env: EnvPtr, args: Vec<Value>) -> NativeFunctionResult,
+    ) -> Self {
+        Self {
+            max_arg_count: Some(max_arg_count),
+            ..Self::new_variadic(name, min_arg_count, func)
+        }
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
 }
 
+/// A script function. The closure is the environment it was declared in, shared rather than
+/// copied, so it sees later changes to captured variables and cloning a function is cheap.
 #[derive(Debug, Clone)]
 pub struct Function {
-    declaration: FunctionStatement,
-    closure: Environment,
+    declaration: Rc<FunctionStatement>,
+    closure: EnvPtr,
 }
 
 impl Function {
-    pub fn new(declaration: FunctionStatement, closure: Environment) -> Self {
+    pub fn new(declaration: Rc<FunctionStatement>, closure: EnvPtr) -> Self {
         Self {
             declaration,
             closure,
         }
     }
+
+    pub fn get_source(&self) -> Option<&str> {
+        self.declaration.source.as_deref()
+    }
 }
 
 impl<'a> Callable<'a> for Function {
-    fn call(&mut self, interpreter: &mut Interpreter, args: Vec<Value>) -> CallableResult {
-        let mut local_env = Environment::new(Some(self.closure.clone().into()));
+    fn call(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> CallableResult {
+        let mut local_env = Environment::new(Some(self.closure.clone()));
         for (param, arg) in self.declaration.params.iter().zip(args.iter()) {
             local_env.define(param.lexeme.clone(), arg.clone());
         }
+        if let Some(rest) = &self.declaration.rest {
+            let extra = args[self.declaration.params.len()..].to_vec();
+            local_env.define(rest.lexeme.clone(), Value::List(SharedPtr::new(extra)));
+        }
         if let MaybeReturn::Return(x) = interpreter
             .execute_block(&self.declaration.body, local_env.into())
             .map_err(|x| RuntimeOrNativeError::Runtime(x))?
@@ -94,10 +133,45 @@ impl<'a> Callable<'a> for Function {
     fn get_name(&self) -> String {
         self.declaration.name.lexeme.clone()
     }
+
+    fn is_variadic(&self) -> bool {
+        self.declaration.rest.is_some()
+    }
+}
+
+// Shows the name and arity, like <fn greet/2>, with a + when any number of further arguments
+// are accepted, or the limit as in <native fn range/2..3>.
+fn fmt_signature<'a>(
+    kind: &str,
+    func: &impl Callable<'a>,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    let rest = match (func.is_variadic(), func.get_max_arity()) {
+        (false, _) => String::new(),
+        (true, None) => "+".to_owned(),
+        (true, Some(max)) => format!("..{max}"),
+    };
+    f.write_fmt(format_args!(
+        "<{kind} {}/{}{rest}>",
+        func.get_name(),
+        func.get_arity()
+    ))
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_signature("fn", self, f)
+    }
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_signature("native fn", self, f)
+    }
 }
 
 impl<'a> Callable<'a> for NativeFunction {
-    fn call(&mut self, interpreter: &mut Interpreter, args: Vec<Value>) -> CallableResult {
+    fn call(&self, interpreter: &mut Interpreter, args: Vec<Value>) -> CallableResult {
         let func_result = (self.func)(interpreter.get_current_env(), args);
         func_result.map_err(|e| RuntimeOrNativeError::Native(e))
     }
@@ -109,4 +183,12 @@ impl<'a> Callable<'a> for NativeFunction {
     fn get_name(&self) -> String {
         self.name.clone()
     }
+
+    fn is_variadic(&self) -> bool {
+        self.variadic
+    }
+
+    fn get_max_arity(&self) -> Option<usize> {
+        self.max_arg_count
+    }
 }
@@ -1,5 +1,8 @@
 use super::{Class, Value};
-use crate::{error::Result, error::RuntimeError, token::Token};
+use crate::{
+    error::{Result, RuntimeError, RuntimeErrorKind},
+    token::Token,
+};
 use std::{collections::HashMap, fmt::Display};
 
 #[derive(Debug, Clone)]
@@ -23,6 +26,7 @@ impl Instance {
             .ok_or_else(|| {
                 RuntimeError::new(
                     name.clone(),
+                    RuntimeErrorKind::UndefinedProperty,
                     format!("Undefined property '{}'.", name.lexeme),
                 )
             })
@@ -1,4 +1,5 @@
 use std::{
+    any::Any,
     cell::{Ref, RefCell, RefMut},
     fmt::{Debug, Display},
     rc::Rc,
@@ -6,21 +7,32 @@ use std::{
 
 mod callable;
 mod class;
+mod foreign;
 mod function;
 mod instance;
 
 pub use {
     callable::{Callable, CallableResult},
     class::Class,
+    foreign::Foreign,
     function::*,
     instance::Instance,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct SharedPtr<T: ?Sized> {
     ptr: Rc<RefCell<T>>,
 }
 
+// Derived Clone would require `T: Clone`, but cloning only copies the pointer.
+impl<T: ?Sized> Clone for SharedPtr<T> {
+    fn clone(&self) -> Self {
+        Self {
+            ptr: self.ptr.clone(),
+        }
+    }
+}
+
 impl<T> SharedPtr<T> {
     pub fn new(val: T) -> Self {
         Self {
@@ -35,18 +47,29 @@ impl<T> SharedPtr<T> {
     pub fn borrow_mut(&self) -> RefMut<'_, T> {
         self.ptr.borrow_mut()
     }
+
+    /// Whether both point to the same object.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.ptr, &other.ptr)
+    }
+
+    /// The address of the object, to tell objects apart without comparing them.
+    pub fn as_ptr(&self) -> *const RefCell<T> {
+        Rc::as_ptr(&self.ptr)
+    }
 }
 
 impl<'a, T> Callable<'a> for SharedPtr<T>
 where
     T: Callable<'a>,
 {
+    // Only borrows immutably, so a function can call itself or print itself while it runs.
     fn call(
-        &mut self,
+        &self,
         interpreter: &mut crate::interpreter::Interpreter,
         args: Vec<Value>,
     ) -> CallableResult {
-        self.ptr.borrow_mut().call(interpreter, args)
+        self.ptr.borrow().call(interpreter, args)
     }
 
     fn get_arity(&self) -> usize {
@@ -56,6 +79,14 @@ where
     fn get_name(&self) -> String {
         self.ptr.borrow().get_name()
     }
+
+    fn is_variadic(&self) -> bool {
+        self.ptr.borrow().is_variadic()
+    }
+
+    fn get_max_arity(&self) -> Option<usize> {
+        self.ptr.borrow().get_max_arity()
+    }
 }
 
 #[derive(Debug)]
@@ -67,6 +98,8 @@ pub enum Value {
     Function(SharedPtr<Function>),
     Class(Class),
     Instance(SharedPtr<Instance>),
+    List(SharedPtr<Vec<Value>>),
+    Foreign(SharedPtr<Foreign>),
     None,
 }
 
@@ -80,23 +113,78 @@ impl Clone for Value {
             Self::NativeFunction(x) => Self::NativeFunction(x.clone()),
             Self::Class(x) => Self::Class(x.clone()),
             Self::Instance(x) => Self::Instance(x.clone()),
+            Self::List(x) => Self::List(x.clone()),
+            Self::Foreign(x) => Self::Foreign(x.clone()),
             Self::None => Self::None,
         }
     }
 }
 
+thread_local! {
+    // Lists being displayed further up the stack.
+    static PRINTING_LISTS: RefCell<Vec<usize>> = const { RefCell::new(vec![]) };
+}
+
+impl Value {
+    pub fn foreign<T: Any>(type_name: &'static str, value: T) -> Self {
+        Self::Foreign(SharedPtr::new(Foreign::new(type_name, value)))
+    }
+
+    /// Runs `func` on the wrapped value if this is a foreign value holding a `T`.
+    pub fn with_foreign<T: Any, R>(&self, func: impl FnOnce(&mut T) -> R) -> Option<R> {
+        match self {
+            Self::Foreign(x) => x.borrow_mut().downcast_mut::<T>().map(func),
+            _ => None,
+        }
+    }
+
+    // Whole numbers print without a fraction. Very large or small ones switch to exponent
+    // notation, at the same bounds JavaScript uses.
+    fn fmt_number(x: f64, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if x == 0.0 {
+            // Also covers -0.
+            f.write_str("0")
+        } else if x.is_finite() && (x.abs() >= 1e21 || x.abs() < 1e-6) {
+            f.write_fmt(format_args!("{x:e}"))
+        } else {
+            f.write_fmt(format_args!("{x}"))
+        }
+    }
+
+    fn fmt_list(items: &[Value], f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[")?;
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            Display::fmt(item, f)?;
+        }
+        f.write_str("]")
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::String(x) => f.write_fmt(format_args!("{x}")),
-            Value::Number(x) => f.write_fmt(format_args!("{x}")),
+            Value::Number(x) => Self::fmt_number(*x, f),
             Value::Boolean(x) => f.write_fmt(format_args!("{x}")),
-            Value::NativeFunction(x) => {
-                f.write_fmt(format_args!("native function {}", x.borrow().get_name()))
-            }
-            Value::Function(x) => f.write_fmt(format_args!("function {}", x.borrow().get_name())),
+            Value::NativeFunction(x) => Display::fmt(&*x.borrow(), f),
+            Value::Function(x) => Display::fmt(&*x.borrow(), f),
             Value::Class(x) => Display::fmt(x, f),
             Value::Instance(x) => Display::fmt(&x.borrow().clone(), f),
+            Value::List(x) => {
+                // A list that contains itself is shown as [...] the second time around.
+                let ptr = x.as_ptr() as usize;
+                if PRINTING_LISTS.with(|lists| lists.borrow().contains(&ptr)) {
+                    return f.write_str("[...]");
+                }
+                PRINTING_LISTS.with(|lists| lists.borrow_mut().push(ptr));
+                let result = Self::fmt_list(&x.borrow(), f);
+                PRINTING_LISTS.with(|lists| lists.borrow_mut().pop());
+                result
+            }
+            Value::Foreign(x) => Display::fmt(&*x.borrow(), f),
             Value::None => f.write_str("none"),
         }
     }